@@ -0,0 +1,258 @@
+//! Search checkpoints for resumable level improvement
+//!
+//! A checkpoint records how far a level improvement search has progressed, so an
+//! interrupted run can continue from the exact counter where it stopped.
+//! Checkpoints are stored as small INI files next to the identity file
+//! (`identity.ini` -> `identity.checkpoint`).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use ini::Ini;
+use thiserror::Error;
+use crate::shard::Shard;
+
+#[derive(Debug, Error)]
+pub enum CheckpointError {
+    #[error("Failed to access checkpoint file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Failed to parse checkpoint file: {0}")]
+    IniError(String),
+
+    #[error("Missing [Checkpoint] section in file")]
+    MissingSection,
+
+    #[error("Missing '{0}' key in [Checkpoint] section")]
+    MissingKey(&'static str),
+
+    #[error("Invalid value for '{0}': {1}")]
+    InvalidValue(&'static str, String),
+
+    #[error("Checkpoint was created for a different public key")]
+    PublicKeyMismatch,
+
+    #[error("Checkpoint was created for shard {0}, not {1}")]
+    ShardMismatch(Shard, Shard),
+
+    #[error("Checkpoint {0} already holds a search of this identity")]
+    AlreadyExists(String),
+}
+
+/// Snapshot of a level improvement search
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Public key (omega) the search is running against
+    pub public_key: String,
+    /// First counter that has not been checked yet (`None` once the search is exhausted)
+    pub next_counter: Option<u64>,
+    /// Best security level found so far
+    pub best_level: u8,
+    /// Counter producing `best_level`
+    pub best_counter: u64,
+    /// Total hashes checked across all runs
+    pub hashes_checked: u64,
+    /// Total search time across all runs
+    pub elapsed_secs: f64,
+    /// Part of the counter space the search is limited to (`1/1` unless sharded)
+    pub shard: Shard,
+}
+
+impl Checkpoint {
+    /// Get the checkpoint path belonging to an identity file: `<dir>/<basename>.checkpoint`
+    pub fn path_for<P: AsRef<Path>>(identity_path: P) -> PathBuf {
+        Self::path_for_shard(identity_path, Shard::ALL)
+    }
+
+    /// Checkpoint path of one shard's search: `<dir>/<basename>.shard2of4.checkpoint`
+    ///
+    /// Shards of the same identity can then run side by side in one directory.
+    pub fn path_for_shard<P: AsRef<Path>>(identity_path: P, shard: Shard) -> PathBuf {
+        let identity_path = identity_path.as_ref();
+        let stem = identity_path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        identity_path.with_file_name(format!("{}{}.checkpoint", stem, shard.file_tag()))
+    }
+
+    /// Load a checkpoint from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CheckpointError> {
+        let conf = Ini::load_from_file(path)
+            .map_err(|e| CheckpointError::IniError(e.to_string()))?;
+
+        let section = conf
+            .section(Some("Checkpoint"))
+            .ok_or(CheckpointError::MissingSection)?;
+
+        let get = |key: &'static str| section.get(key).ok_or(CheckpointError::MissingKey(key));
+
+        fn parse<T: std::str::FromStr>(key: &'static str, value: &str) -> Result<T, CheckpointError>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.trim().parse::<T>()
+                .map_err(|e| CheckpointError::InvalidValue(key, e.to_string()))
+        }
+
+        // An exhausted search is stored with an empty next_counter
+        let next_counter = get("next_counter")?.trim();
+
+        Ok(Self {
+            public_key: get("public_key")?.trim().to_string(),
+            next_counter: match next_counter {
+                "" => None,
+                value => Some(parse("next_counter", value)?),
+            },
+            best_level: parse("best_level", get("best_level")?)?,
+            best_counter: parse("best_counter", get("best_counter")?)?,
+            hashes_checked: parse("hashes_checked", get("hashes_checked")?)?,
+            elapsed_secs: parse("elapsed_secs", get("elapsed_secs")?)?,
+            // Checkpoints of unsharded searches have no shard key
+            shard: section.get("shard").map(|value| parse("shard", value)).transpose()?.unwrap_or_default(),
+        })
+    }
+
+    /// Load a checkpoint and make sure it was created for the given public key
+    pub fn load_for_key<P: AsRef<Path>>(path: P, public_key: &str) -> Result<Self, CheckpointError> {
+        let checkpoint = Self::load(path)?;
+        if checkpoint.public_key != public_key {
+            return Err(CheckpointError::PublicKeyMismatch);
+        }
+        Ok(checkpoint)
+    }
+
+    /// Save the checkpoint to a file
+    ///
+    /// The data is written to a temporary file first and then renamed over the target,
+    /// so an interrupted write never leaves a truncated checkpoint behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+
+        let mut conf = Ini::new();
+        conf.with_section(Some("Checkpoint"))
+            .set("public_key", self.public_key.as_str())
+            .set("next_counter", self.next_counter.map(|c| c.to_string()).unwrap_or_default())
+            .set("best_level", self.best_level.to_string())
+            .set("best_counter", self.best_counter.to_string())
+            .set("hashes_checked", self.hashes_checked.to_string())
+            .set("elapsed_secs", format!("{:.3}", self.elapsed_secs));
+        if !self.shard.is_all() {
+            conf.with_section(Some("Checkpoint")).set("shard", self.shard.to_string());
+        }
+
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_name);
+
+        conf.write_to_file(&tmp_path)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ts3-sec-{}-{}.checkpoint", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_path_for() {
+        assert_eq!(
+            Checkpoint::path_for("inis/level8.ini"),
+            PathBuf::from("inis/level8.checkpoint")
+        );
+        assert_eq!(
+            Checkpoint::path_for_shard("inis/level8.ini", Shard::new(2, 4).unwrap()),
+            PathBuf::from("inis/level8.shard2of4.checkpoint")
+        );
+    }
+
+    #[test]
+    fn test_save_load_roundtrip() {
+        let path = temp_path("roundtrip");
+        let checkpoint = Checkpoint {
+            public_key: TEST_PUBLIC_KEY.to_string(),
+            next_counter: Some(18_446_744_073_709_551_000),
+            best_level: 12,
+            best_counter: 672,
+            hashes_checked: 123_456_789,
+            elapsed_secs: 42.5,
+            shard: Shard::ALL,
+        };
+
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load_for_key(&path, TEST_PUBLIC_KEY).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(loaded, checkpoint);
+    }
+
+    #[test]
+    fn test_sharded_roundtrip() {
+        let path = temp_path("sharded");
+        let checkpoint = Checkpoint {
+            public_key: TEST_PUBLIC_KEY.to_string(),
+            next_counter: Some(3 * crate::shard::SHARD_BLOCK_SIZE + 7),
+            best_level: 12,
+            best_counter: 672,
+            hashes_checked: 10_000,
+            elapsed_secs: 1.5,
+            shard: Shard::new(4, 5).unwrap(),
+        };
+
+        checkpoint.save(&path).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let loaded = Checkpoint::load_for_key(&path, TEST_PUBLIC_KEY).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert!(content.contains("shard=4/5"));
+        assert_eq!(loaded, checkpoint);
+    }
+
+    #[test]
+    fn test_exhausted_roundtrip() {
+        let path = temp_path("exhausted");
+        let checkpoint = Checkpoint {
+            public_key: TEST_PUBLIC_KEY.to_string(),
+            next_counter: None,
+            best_level: 14,
+            best_counter: 5560,
+            hashes_checked: 251,
+            elapsed_secs: 0.5,
+            shard: Shard::ALL,
+        };
+
+        checkpoint.save(&path).unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let loaded = Checkpoint::load_for_key(&path, TEST_PUBLIC_KEY).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert!(content.contains("next_counter=\n"));
+        assert_eq!(loaded, checkpoint);
+    }
+
+    #[test]
+    fn test_load_rejects_other_public_key() {
+        let path = temp_path("mismatch");
+        let checkpoint = Checkpoint {
+            public_key: TEST_PUBLIC_KEY.to_string(),
+            next_counter: Some(1000),
+            best_level: 9,
+            best_counter: 201,
+            hashes_checked: 1000,
+            elapsed_secs: 1.0,
+            shard: Shard::ALL,
+        };
+
+        checkpoint.save(&path).unwrap();
+        let result = Checkpoint::load_for_key(&path, "ME0DAgcAAgEgAiEAother");
+        fs::remove_file(&path).ok();
+
+        assert!(matches!(result, Err(CheckpointError::PublicKeyMismatch)));
+    }
+}
@@ -1,6 +1,8 @@
 //! Command-line interface definitions
 
 use clap::{Parser, Subcommand, ValueEnum};
+use crate::output::OutputFormat;
+use crate::shard::Shard;
 
 #[derive(Parser)]
 #[command(name = "ts3-sec-cuda-rs")]
@@ -8,6 +10,10 @@ use clap::{Parser, Subcommand, ValueEnum};
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+
+    /// Output format: human-readable text or one JSON record per line
+    #[arg(short, long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    pub output: OutputFormat,
 }
 
 #[derive(Subcommand)]
@@ -21,6 +27,18 @@ pub enum Command {
         /// Identity string directly (format: "counter" + "V" + base64_key)
         #[arg(short, long, group = "input")]
         string: Option<String>,
+
+        /// Copy of the TS3 client's settings.db (lists its identities without --nickname)
+        #[arg(long, group = "input")]
+        db: Option<String>,
+
+        /// Nickname or identity name of the settings.db identity to use
+        #[arg(long, requires = "db")]
+        nickname: Option<String>,
+
+        /// Check whether the identity matches this UID (e.g. from a server permission export)
+        #[arg(long)]
+        uid: Option<String>,
     },
     /// Increase security level of an identity
     Increase {
@@ -32,6 +50,22 @@ pub enum Command {
         #[arg(short, long, group = "input")]
         string: Option<String>,
 
+        /// Copy of the TS3 client's settings.db (requires --nickname)
+        #[arg(long, group = "input", requires = "nickname")]
+        db: Option<String>,
+
+        /// Nickname or identity name of the settings.db identity to use
+        #[arg(long, requires = "db")]
+        nickname: Option<String>,
+
+        /// Search with only the public key (omega, base64); prints the winning counter
+        #[arg(long, group = "input", requires = "counter")]
+        omega: Option<String>,
+
+        /// Current counter of the --omega identity (the search starts after it)
+        #[arg(short, long, requires = "omega")]
+        counter: Option<u64>,
+
         /// Target security level to reach
         #[arg(short, long)]
         target: u8,
@@ -54,13 +88,329 @@ pub enum Command {
         /// Only needed if you want to override the automatic calculation
         #[arg(long)]
         cuda_shared_mem: Option<usize>,
+
+        /// Resume from the checkpoint next to the identity file (requires --file)
+        #[arg(long, requires = "file")]
+        resume: bool,
+
+        /// Start over even if a checkpoint of this identity exists (it gets overwritten)
+        #[arg(long, requires = "file", conflicts_with = "resume")]
+        fresh: bool,
+
+        /// Write the improved counter into this settings.db copy afterwards
+        /// (matched by public key; a timestamped backup is made first)
+        #[arg(long, conflicts_with = "omega")]
+        export_db: Option<String>,
+
+        /// Only search part i of n of the counter space (e.g. 2/4), so n machines never overlap
+        #[arg(long)]
+        shard: Option<Shard>,
+    },
+    /// Increase the security level of many identities with one hasher
+    IncreaseAll {
+        /// Directory of identity .ini files (all raised to --target)
+        #[arg(short, long, group = "source", required_unless_present = "manifest")]
+        dir: Option<String>,
+
+        /// Manifest INI with one section per identity: file, target, priority
+        #[arg(long, group = "source")]
+        manifest: Option<String>,
+
+        /// Target security level (default for manifest entries without `target`)
+        #[arg(short, long, required_unless_present = "manifest")]
+        target: Option<u8>,
+
+        /// Raise every identity to each of these levels before going higher (e.g. 24,30)
+        #[arg(long, value_delimiter = ',')]
+        stages: Vec<u8>,
+
+        /// Hasher method to use
+        #[arg(short = 'm', long, value_enum, default_value_t = HasherMethod::Cpu)]
+        method: HasherMethod,
+
+        /// Batch size for processing (CPU: 10000, CUDA: 4000000)
+        #[arg(short, long)]
+        batch_size: Option<usize>,
+
+        /// CUDA threads per block (32, 64, 128, 256, 512, 1024) [default: 128]
+        #[arg(long)]
+        cuda_threads: Option<usize>,
+
+        /// CUDA dynamic shared memory in bytes [default: threads_per_block * 128]
+        #[arg(long)]
+        cuda_shared_mem: Option<usize>,
+    },
+    /// Generate a new identity.ini with a fresh key pair
+    Generate {
+        /// Path of the identity.ini file to create
+        #[arg(short, long, default_value = "identity.ini")]
+        file: String,
+
+        /// Identity name shown in the client's identity manager (the `id` field)
+        #[arg(long, default_value = "Generated")]
+        name: String,
+
+        /// Default nickname for the identity
+        #[arg(short, long, default_value = "TeamSpeakUser")]
+        nickname: String,
+
+        /// Phonetic nickname for the identity
+        #[arg(long, default_value = "")]
+        phonetic_nickname: String,
+
+        /// Overwrite the output file if it already exists
+        #[arg(long)]
+        force: bool,
+
+        /// Raise the new identity to this security level right away
+        #[arg(short, long)]
+        target: Option<u8>,
+
+        /// Hasher method to use when --target is given
+        #[arg(short = 'm', long, value_enum, default_value_t = HasherMethod::Cpu)]
+        method: HasherMethod,
+
+        /// Batch size for processing (CPU: 10000, CUDA: 4000000)
+        #[arg(short, long)]
+        batch_size: Option<usize>,
+
+        /// CUDA threads per block (32, 64, 128, 256, 512, 1024) [default: 128]
+        #[arg(long)]
+        cuda_threads: Option<usize>,
+
+        /// CUDA dynamic shared memory in bytes [default: threads_per_block * 128]
+        #[arg(long)]
+        cuda_shared_mem: Option<usize>,
+    },
+    /// Write a work package (public key, counter, target) for an offline worker
+    ExportWork {
+        /// Path to identity.ini file
+        #[arg(short, long)]
+        file: String,
+
+        /// Target security level for the worker
+        #[arg(short, long)]
+        target: u8,
+
+        /// Package path [default: <identity>.work]
+        #[arg(long)]
+        out: Option<String>,
+    },
+    /// Search a work package and write a result file (no private key needed)
+    RunWork {
+        /// Work package written by export-work
+        #[arg(short, long)]
+        package: String,
+
+        /// Result path [default: <package>.result]
+        #[arg(long)]
+        out: Option<String>,
+
+        /// Hasher method to use
+        #[arg(short = 'm', long, value_enum, default_value_t = HasherMethod::Cpu)]
+        method: HasherMethod,
+
+        /// Batch size for processing (CPU: 10000, CUDA: 4000000)
+        #[arg(short, long)]
+        batch_size: Option<usize>,
+
+        /// CUDA threads per block (32, 64, 128, 256, 512, 1024) [default: 128]
+        #[arg(long)]
+        cuda_threads: Option<usize>,
+
+        /// CUDA dynamic shared memory in bytes [default: threads_per_block * 128]
+        #[arg(long)]
+        cuda_shared_mem: Option<usize>,
+    },
+    /// Re-check a worker's result and put its counter into the identity file
+    ImportResult {
+        /// Identity file the package was exported from
+        #[arg(short, long)]
+        file: String,
+
+        /// Result file written by run-work
+        #[arg(short, long)]
+        result: String,
+    },
+    /// Re-verify the identity files of sharded runs and keep the best counter
+    Merge {
+        /// Identity file to put the best counter into
+        #[arg(short, long)]
+        file: String,
+
+        /// Identity files written by the shards (e.g. identity-24.ini from each machine)
+        #[arg(required = true)]
+        shards: Vec<String>,
     },
+    /// Hand out counter ranges of a public key to workers over TCP
+    Coordinate {
+        /// Public key (omega) as base64, as shown by decode
+        #[arg(long)]
+        omega: String,
+
+        /// Current counter of the identity; the search starts after it
+        #[arg(short, long)]
+        counter: u64,
+
+        /// Target security level
+        #[arg(short, long)]
+        target: u8,
+
+        /// Address to listen on
+        #[arg(short, long, default_value = "0.0.0.0:7878")]
+        listen: String,
+
+        /// Counters per assigned range
+        #[arg(long, default_value_t = 1_000_000_000)]
+        range_size: u64,
+
+        /// Seconds a worker may stay silent before its range is reassigned
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+
+        /// Workers report every counter with at least this level as evidence of their work
+        #[arg(long, default_value_t = 12, value_parser = clap::value_parser!(u8).range(1..=40))]
+        evidence_level: u8,
+
+        /// Evidence hits re-hashed per worker report
+        #[arg(long, default_value_t = 4)]
+        sample_size: usize,
+
+        /// State file for restarting the coordinator
+        #[arg(long, default_value = "coordinator.state")]
+        state: String,
+    },
+    /// Search counter ranges handed out by a coordinator (no private key needed)
+    Worker {
+        /// Coordinator address, e.g. 192.168.1.10:7878
+        #[arg(long)]
+        connect: String,
+
+        /// Name shown by the coordinator [default: $HOSTNAME]
+        #[arg(long)]
+        name: Option<String>,
+
+        /// Hasher method to use
+        #[arg(short = 'm', long, value_enum, default_value_t = HasherMethod::Cpu)]
+        method: HasherMethod,
+
+        /// Batch size for processing (CPU: 10000, CUDA: 4000000)
+        #[arg(short, long)]
+        batch_size: Option<usize>,
+
+        /// CUDA threads per block (32, 64, 128, 256, 512, 1024) [default: 128]
+        #[arg(long)]
+        cuda_threads: Option<usize>,
+
+        /// CUDA dynamic shared memory in bytes [default: threads_per_block * 128]
+        #[arg(long)]
+        cuda_shared_mem: Option<usize>,
+    },
+    /// Verify the security level of a public key and counter (no private key needed)
+    Verify {
+        /// Public key (omega) as base64, e.g. from the client's clientinitiv
+        #[arg(long)]
+        omega: String,
+
+        /// Counter (key offset) claimed by the client
+        #[arg(short, long)]
+        counter: u64,
+
+        /// Check that omega belongs to this UID (e.g. from a server export)
+        #[arg(long)]
+        uid: Option<String>,
+
+        /// Fail unless the level is at least this high
+        #[arg(long)]
+        min_level: Option<u8>,
+    },
+    /// Estimate how many hashes and how much time reaching a security level takes
+    Estimate {
+        /// Identity file to take the current level (and calibration key) from
+        #[arg(short, long, group = "level", required_unless_present = "current")]
+        file: Option<String>,
+
+        /// Current security level
+        #[arg(short, long, group = "level")]
+        current: Option<u8>,
+
+        /// Target security level
+        #[arg(short, long)]
+        target: u8,
+
+        /// Known hashrate in hashes/sec, e.g. 2150000000, 2.15G or 15M (skips calibration)
+        #[arg(long, value_parser = parse_hashrate)]
+        hashrate: Option<f64>,
+
+        /// Hasher method to calibrate when no --hashrate is given
+        #[arg(short = 'm', long, value_enum, default_value_t = HasherMethod::Cpu)]
+        method: HasherMethod,
+
+        /// Length of the calibration run in seconds
+        #[arg(long, default_value_t = 3.0)]
+        calibration_secs: f64,
+
+        /// Batch size for processing (CPU: 10000, CUDA: 4000000)
+        #[arg(short, long)]
+        batch_size: Option<usize>,
+
+        /// CUDA threads per block (32, 64, 128, 256, 512, 1024) [default: 128]
+        #[arg(long)]
+        cuda_threads: Option<usize>,
+
+        /// CUDA dynamic shared memory in bytes [default: threads_per_block * 128]
+        #[arg(long)]
+        cuda_shared_mem: Option<usize>,
+    },
+}
+
+/// Parse a hashrate with an optional k/M/G/T suffix (e.g. `2.15G` = 2.15 billion hashes/sec)
+fn parse_hashrate(value: &str) -> Result<f64, String> {
+    let value = value.trim().trim_end_matches("H/s").trim_end_matches("h/s").trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&value[..i], 1e3),
+        Some((i, 'M')) => (&value[..i], 1e6),
+        Some((i, 'G')) => (&value[..i], 1e9),
+        Some((i, 'T')) => (&value[..i], 1e12),
+        _ => (value, 1.0),
+    };
+
+    let hashes_per_sec = number.trim().parse::<f64>()
+        .map_err(|_| format!("invalid hashrate '{}'", value))? * multiplier;
+    if hashes_per_sec.is_finite() && hashes_per_sec > 0.0 {
+        Ok(hashes_per_sec)
+    } else {
+        Err("hashrate must be positive".to_string())
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum HasherMethod {
     /// CPU-based SHA-1 hashing
     Cpu,
-    /// CUDA/GPU-based SHA-1 hashing
+    /// CPU-based SHA-1 hashing with the public key prefix precomputed once per batch
+    CpuMidstate,
+    /// CPU-based SHA-1 hashing in SIMD lanes (SSE2/AVX2/AVX-512, picked at runtime)
+    CpuSimd,
+    /// CPU-based midstate SHA-1 hashing, reporting whether the x86 SHA extensions (SHA-NI) are used
+    CpuShani,
+    /// CUDA/GPU-based SHA-1 hashing (requires the `cuda` feature)
+    #[cfg_attr(not(feature = "cuda"), value(hide = true))]
     Cuda,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hashrate() {
+        assert_eq!(parse_hashrate("2150000000"), Ok(2.15e9));
+        assert_eq!(parse_hashrate("2.15G"), Ok(2.15e9));
+        assert_eq!(parse_hashrate("15M"), Ok(15e6));
+        assert_eq!(parse_hashrate("800k"), Ok(800e3));
+        assert_eq!(parse_hashrate("15 MH/s"), Ok(15e6));
+        assert!(parse_hashrate("fast").is_err());
+        assert!(parse_hashrate("0").is_err());
+    }
+}
@@ -0,0 +1,1062 @@
+//! Coordinator side of the distributed search
+//!
+//! The coordinator listens for workers, hands each one a range of counters and
+//! tracks which counters have been checked. Hits are re-verified with
+//! [`verify_security_level`] before they count, and the evidence reported with each
+//! range is spot-checked (see [`evidence`](super::evidence)); ranges that fail go
+//! back into the pool for another worker. After every change the state is
+//! written to the state file (if configured), so a restarted coordinator continues
+//! where it stopped: ranges that were still assigned go back into the pool from the
+//! last counter their worker reported.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufReader, ErrorKind};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use ini::Ini;
+
+use super::evidence::{check_evidence_count, FlagReason, Sampler, DEFAULT_EVIDENCE_LEVEL, DEFAULT_SAMPLE_SIZE};
+use super::{merge_ranges, receive, send, CoordinatorMessage, CounterRange, DistributedError, WorkerMessage};
+use crate::hashers::CpuHasher;
+use crate::level_improver::{LevelSearchResult, SearchOutcome, SecurityLevelHasher, StopToken};
+use crate::verify::verify_security_level;
+
+/// Default number of counters handed out per range
+pub const DEFAULT_RANGE_SIZE: u64 = 1_000_000_000;
+
+/// Default time a worker may stay silent before its range is reassigned
+pub const DEFAULT_WORKER_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest time idle workers are told to wait before asking again
+const WAIT_MILLIS: u64 = 500;
+
+/// How often the accept loop checks the stop token and stale ranges
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How often a status event is emitted while the coordinator runs
+const STATUS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Settings for a coordinator
+#[derive(Debug, Clone)]
+pub struct CoordinatorConfig {
+    /// Public key (omega) to search counters for
+    pub public_key: String,
+    /// Current counter of the identity; the search starts right after it
+    pub counter: u64,
+    /// Stop once a counter with at least this level has been found
+    pub target_level: u8,
+    /// Number of counters per assigned range
+    pub range_size: u64,
+    /// Reassign a range when its worker is silent for this long
+    pub worker_timeout: Duration,
+    /// Workers report every counter with at least this level as evidence
+    pub evidence_level: u8,
+    /// Evidence hits re-hashed per report
+    pub sample_size: usize,
+    /// File the coordinator state is persisted to
+    pub state_path: Option<PathBuf>,
+}
+
+impl CoordinatorConfig {
+    /// Config with default range size, timeout and spot checks and no state file
+    pub fn new(public_key: &str, counter: u64, target_level: u8) -> Self {
+        Self {
+            public_key: public_key.trim().to_string(),
+            counter,
+            target_level,
+            range_size: DEFAULT_RANGE_SIZE,
+            worker_timeout: DEFAULT_WORKER_TIMEOUT,
+            evidence_level: DEFAULT_EVIDENCE_LEVEL,
+            sample_size: DEFAULT_SAMPLE_SIZE,
+            state_path: None,
+        }
+    }
+}
+
+/// Persistent coordinator state
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoordinatorState {
+    /// Public key (omega) the search is running against
+    pub public_key: String,
+    /// Target level of the search
+    pub target_level: u8,
+    /// First counter that has never been handed out, `None` once the counter space is used up
+    pub next_counter: Option<u64>,
+    /// Ranges waiting to be (re)assigned
+    pub pending: Vec<CounterRange>,
+    /// Unchecked part of the ranges currently held by workers
+    pub assigned: Vec<CounterRange>,
+    /// Ranges that have been checked completely
+    pub completed: Vec<CounterRange>,
+    /// Best security level found so far
+    pub best_level: u8,
+    /// Counter producing `best_level`
+    pub best_counter: u64,
+    /// Total hashes reported by workers across all runs
+    pub hashes_checked: u64,
+}
+
+impl CoordinatorState {
+    /// Fresh state for a search starting after `config.counter`
+    pub fn new(config: &CoordinatorConfig) -> Self {
+        Self {
+            public_key: config.public_key.clone(),
+            target_level: config.target_level,
+            next_counter: config.counter.checked_add(1),
+            pending: Vec::new(),
+            assigned: Vec::new(),
+            completed: Vec::new(),
+            best_level: verify_security_level(&config.public_key, config.counter),
+            best_counter: config.counter,
+            hashes_checked: 0,
+        }
+    }
+
+    /// Load the state from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, DistributedError> {
+        let conf = Ini::load_from_file(path)
+            .map_err(|e| DistributedError::IniError(e.to_string()))?;
+
+        let section = conf
+            .section(Some("Coordinator"))
+            .ok_or(DistributedError::MissingSection)?;
+
+        let get = |key: &'static str| section.get(key).ok_or(DistributedError::MissingKey(key));
+
+        fn parse<T: std::str::FromStr>(key: &'static str, value: &str) -> Result<T, DistributedError>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.trim().parse::<T>()
+                .map_err(|e| DistributedError::InvalidValue(key, e.to_string()))
+        }
+
+        fn parse_ranges(key: &'static str, value: &str) -> Result<Vec<CounterRange>, DistributedError> {
+            value.split(',')
+                .filter(|part| !part.trim().is_empty())
+                .map(|part| parse(key, part))
+                .collect()
+        }
+
+        let next_counter = get("next_counter")?.trim();
+
+        Ok(Self {
+            public_key: get("public_key")?.trim().to_string(),
+            target_level: parse("target_level", get("target_level")?)?,
+            next_counter: match next_counter {
+                "" => None,
+                value => Some(parse("next_counter", value)?),
+            },
+            pending: parse_ranges("pending", get("pending")?)?,
+            assigned: parse_ranges("assigned", get("assigned")?)?,
+            completed: parse_ranges("completed", get("completed")?)?,
+            best_level: parse("best_level", get("best_level")?)?,
+            best_counter: parse("best_counter", get("best_counter")?)?,
+            hashes_checked: parse("hashes_checked", get("hashes_checked")?)?,
+        })
+    }
+
+    /// Save the state to a file
+    ///
+    /// Written to a temporary file first and renamed over the target, like checkpoints.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DistributedError> {
+        let path = path.as_ref();
+
+        fn join(ranges: &[CounterRange]) -> String {
+            ranges.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(",")
+        }
+
+        let mut conf = Ini::new();
+        conf.with_section(Some("Coordinator"))
+            .set("public_key", self.public_key.as_str())
+            .set("target_level", self.target_level.to_string())
+            .set("next_counter", self.next_counter.map(|c| c.to_string()).unwrap_or_default())
+            .set("pending", join(&self.pending))
+            .set("assigned", join(&self.assigned))
+            .set("completed", join(&self.completed))
+            .set("best_level", self.best_level.to_string())
+            .set("best_counter", self.best_counter.to_string())
+            .set("hashes_checked", self.hashes_checked.to_string());
+
+        let mut tmp_name = path.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_name);
+
+        conf.write_to_file(&tmp_path)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Best counter and level found so far
+    pub fn best(&self) -> LevelSearchResult {
+        LevelSearchResult { counter: self.best_counter, level: self.best_level }
+    }
+
+    /// Total number of counters in completed ranges
+    pub fn completed_counters(&self) -> u128 {
+        self.completed.iter().map(|r| r.count() as u128).sum()
+    }
+}
+
+/// Something that happened on the coordinator
+#[derive(Debug, Clone, PartialEq)]
+pub enum CoordinatorEvent {
+    /// A worker connected
+    WorkerJoined { worker: String, hasher: String },
+    /// A range was handed to a worker
+    RangeAssigned { worker: String, range: CounterRange },
+    /// A worker checked its whole range
+    RangeCompleted { worker: String, range: CounterRange },
+    /// A worker disconnected or timed out; the unchecked part goes back into the pool
+    RangeReleased { worker: String, range: CounterRange },
+    /// A verified hit improved the best level
+    NewLevel { worker: String, counter: u64, level: u8, hashes_checked: u64 },
+    /// A reported hit did not verify; the worker's range was taken away
+    HitRejected { worker: String, counter: u64, claimed: u8, actual: u8 },
+    /// A worker's results for a range could not be trusted; the whole range is searched again
+    RangeFlagged { worker: String, range: CounterRange, reason: FlagReason },
+    /// Periodic summary while running
+    Status { workers: usize, best_level: u8, best_counter: u64, hashes_checked: u64, hashes_per_sec: f64 },
+}
+
+/// A range held by a worker
+struct Assignment {
+    range: CounterRange,
+    next: u64, // First counter of `range` not reported as checked yet
+    worker: String,
+    last_seen: Instant,
+    evidence: u64, // Evidence hits reported for `range.start..next`
+    highest_evidence: Option<u8>,
+}
+
+/// Mutable coordinator data shared by all connections
+struct Shared {
+    state: CoordinatorState, // `assigned` is kept empty; see `snapshot`
+    assignments: HashMap<u64, Assignment>,
+    flagged: HashMap<CounterRange, String>, // Pending ranges and the worker that failed them
+    sampler: Sampler,
+    next_range_id: u64,
+    connections: usize,
+    save_error: Option<DistributedError>,
+    on_event: Box<dyn Fn(&CoordinatorEvent) + Send>,
+}
+
+impl Shared {
+    fn emit(&self, event: CoordinatorEvent) {
+        (self.on_event)(&event);
+    }
+
+    /// Outcome once the search is over
+    fn finished(&self, target_level: u8) -> Option<SearchOutcome> {
+        if self.state.best_level >= target_level {
+            Some(SearchOutcome::TargetReached(self.state.best()))
+        } else if self.state.next_counter.is_none() && self.state.pending.is_empty() && self.assignments.is_empty() {
+            Some(SearchOutcome::Exhausted)
+        } else {
+            None
+        }
+    }
+
+    fn done_message(&self) -> CoordinatorMessage {
+        CoordinatorMessage::Done { best_counter: self.state.best_counter, best_level: self.state.best_level }
+    }
+
+    /// Full state including the ranges currently held by workers
+    fn snapshot(&self) -> CoordinatorState {
+        let mut state = self.state.clone();
+        state.assigned = self.assignments.values()
+            .map(|a| CounterRange::new(a.next, a.range.end))
+            .collect();
+        state.assigned.sort();
+        state
+    }
+
+    /// Take a range away from its worker
+    ///
+    /// The part before the last reported counter counts as completed if its evidence
+    /// is plausible, the rest is queued for reassignment.
+    fn release(&mut self, range_id: u64, config: &CoordinatorConfig) {
+        let Some(assignment) = self.assignments.remove(&range_id) else {
+            return;
+        };
+
+        let checked = assignment.next - assignment.range.start;
+        if checked > 0
+            && let Err(reason) = check_evidence_count(checked, assignment.evidence, config.evidence_level)
+        {
+            self.flag(assignment, reason);
+            return;
+        }
+
+        if checked > 0 {
+            self.state.completed.push(CounterRange::new(assignment.range.start, assignment.next - 1));
+            merge_ranges(&mut self.state.completed);
+        }
+
+        let remaining = CounterRange::new(assignment.next, assignment.range.end);
+        self.state.pending.push(remaining);
+        self.state.pending.sort();
+        self.emit(CoordinatorEvent::RangeReleased { worker: assignment.worker, range: remaining });
+    }
+
+    /// Queue a whole range for rescanning, away from the worker that failed it
+    fn flag(&mut self, assignment: Assignment, reason: FlagReason) {
+        self.state.pending.push(assignment.range);
+        self.state.pending.sort();
+        self.flagged.insert(assignment.range, assignment.worker.clone());
+        self.emit(CoordinatorEvent::RangeFlagged { worker: assignment.worker, range: assignment.range, reason });
+    }
+
+    /// Release every range whose worker has been silent for longer than the timeout
+    fn expire_stale(&mut self, config: &CoordinatorConfig) {
+        let stale: Vec<u64> = self.assignments.iter()
+            .filter(|(_, a)| a.last_seen.elapsed() > config.worker_timeout)
+            .map(|(&id, _)| id)
+            .collect();
+        for range_id in stale {
+            self.release(range_id, config);
+        }
+    }
+
+    /// Check the evidence for the counters of a range before `until` (`None` for the rest of
+    /// the range) and add it to the assignment
+    ///
+    /// Every evidence hit must lie in those counters; a random sample plus the highest one
+    /// are re-hashed with the [`CpuHasher`].
+    fn record_evidence(
+        &mut self,
+        range_id: u64,
+        until: Option<u64>,
+        evidence: &[(u64, u8)],
+        config: &CoordinatorConfig,
+    ) -> Result<(), FlagReason> {
+        let Some(assignment) = self.assignments.get(&range_id) else {
+            return Ok(());
+        };
+
+        let first = assignment.next;
+        let last = match until {
+            Some(next) => next.checked_sub(1).map(|last| last.min(assignment.range.end)),
+            None => Some(assignment.range.end),
+        };
+        let well_formed = evidence.windows(2).all(|pair| pair[0].0 < pair[1].0)
+            && evidence.iter().all(|&(counter, level)| {
+                counter >= first && last.is_some_and(|last| counter <= last) && level >= config.evidence_level
+            });
+        if !well_formed {
+            return Err(FlagReason::MalformedEvidence);
+        }
+
+        let mut sample = self.sampler.pick(evidence.len(), config.sample_size);
+        sample.extend((0..evidence.len()).max_by_key(|&i| evidence[i].1));
+        sample.sort_unstable();
+        sample.dedup();
+        let counters: Vec<u64> = sample.iter().map(|&i| evidence[i].0).collect();
+        let levels = CpuHasher.calculate_levels_batch(&self.state.public_key, &counters);
+        for (&i, &actual) in sample.iter().zip(&levels) {
+            let (counter, claimed) = evidence[i];
+            if actual != claimed {
+                return Err(FlagReason::WrongLevel { counter, claimed, actual });
+            }
+        }
+
+        if let Some(assignment) = self.assignments.get_mut(&range_id) {
+            assignment.evidence += evidence.len() as u64;
+            assignment.highest_evidence = assignment.highest_evidence.max(evidence.iter().map(|&(_, level)| level).max());
+        }
+        Ok(())
+    }
+
+    /// Check a completed range's evidence against its size and the claimed highest level
+    fn check_completed(&self, range_id: u64, highest_level: u8, config: &CoordinatorConfig) -> Result<(), FlagReason> {
+        let Some(assignment) = self.assignments.get(&range_id) else {
+            return Ok(());
+        };
+
+        let expected_highest = (highest_level >= config.evidence_level).then_some(highest_level);
+        if assignment.highest_evidence != expected_highest {
+            return Err(FlagReason::HighestLevelMismatch { claimed: highest_level, evidence: assignment.highest_evidence });
+        }
+        check_evidence_count(assignment.range.count(), assignment.evidence, config.evidence_level)
+    }
+
+    /// Mark a range as alive, optionally recording how far it got
+    fn touch(&mut self, range_id: u64, next_counter: Option<u64>) -> bool {
+        let Some(assignment) = self.assignments.get_mut(&range_id) else {
+            return false;
+        };
+        assignment.last_seen = Instant::now();
+        if let Some(next) = next_counter
+            && (assignment.next..=assignment.range.end).contains(&next)
+        {
+            assignment.next = next;
+        }
+        true
+    }
+
+    /// Hand the next free range to `worker`
+    fn assign(&mut self, worker: &str, config: &CoordinatorConfig) -> CoordinatorMessage {
+        self.expire_stale(config);
+        if self.finished(config.target_level).is_some() {
+            return self.done_message();
+        }
+
+        // Flagged ranges go to anyone but the worker that failed them
+        let pending = self.state.pending.iter()
+            .position(|range| self.flagged.get(range).is_none_or(|flagged_by| flagged_by != worker));
+
+        let range = if let Some(index) = pending {
+            let range = self.state.pending.remove(index);
+            self.flagged.remove(&range);
+            range
+        } else if let Some(start) = self.state.next_counter {
+            let end = start.saturating_add(config.range_size.max(1) - 1);
+            self.state.next_counter = end.checked_add(1);
+            CounterRange::new(start, end)
+        } else {
+            // Everything is handed out, but a range may still come back from a silent worker.
+            // Idle workers must ask again before their own connection times out.
+            let millis = (config.worker_timeout.as_millis() as u64 / 3).clamp(1, WAIT_MILLIS);
+            return CoordinatorMessage::Wait { millis };
+        };
+
+        let range_id = self.next_range_id;
+        self.next_range_id += 1;
+        self.assignments.insert(range_id, Assignment {
+            range,
+            next: range.start,
+            worker: worker.to_string(),
+            last_seen: Instant::now(),
+            evidence: 0,
+            highest_evidence: None,
+        });
+        self.emit(CoordinatorEvent::RangeAssigned { worker: worker.to_string(), range });
+
+        CoordinatorMessage::Assign {
+            range_id,
+            public_key: self.state.public_key.clone(),
+            start: range.start,
+            end: range.end,
+            best_level: self.state.best_level,
+            target_level: config.target_level,
+            evidence_level: config.evidence_level,
+        }
+    }
+}
+
+/// Per-connection bookkeeping
+struct Session {
+    worker: String,
+    range_id: Option<u64>,
+}
+
+/// Coordinator data shared between the accept loop and connection threads
+struct Hub {
+    config: CoordinatorConfig,
+    shared: Mutex<Shared>,
+}
+
+impl Hub {
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, shared: &mut Shared) {
+        if let Some(path) = &self.config.state_path
+            && let Err(e) = shared.snapshot().save(path)
+        {
+            shared.save_error = Some(e);
+        }
+    }
+
+    /// Answer one worker message
+    fn handle(&self, session: &mut Session, message: WorkerMessage) -> CoordinatorMessage {
+        let config = &self.config;
+        let mut shared = self.lock();
+
+        // A worker may only report on the range it holds, never on someone else's
+        if let WorkerMessage::Progress { range_id, .. }
+            | WorkerMessage::Hit { range_id, .. }
+            | WorkerMessage::Complete { range_id, .. } = &message
+            && session.range_id != Some(*range_id)
+        {
+            return CoordinatorMessage::Revoked;
+        }
+
+        let reply = match message {
+            WorkerMessage::Hello { worker, hasher } => {
+                session.worker = worker;
+                shared.emit(CoordinatorEvent::WorkerJoined { worker: session.worker.clone(), hasher });
+                shared.assign(&session.worker, config)
+            }
+            WorkerMessage::Request => shared.assign(&session.worker, config),
+            WorkerMessage::Progress { range_id, next_counter, hashes, evidence } => {
+                shared.state.hashes_checked = shared.state.hashes_checked.saturating_add(hashes);
+                if let Err(reason) = shared.record_evidence(range_id, Some(next_counter), &evidence, config)
+                    && let Some(assignment) = shared.assignments.remove(&range_id)
+                {
+                    shared.flag(assignment, reason);
+                }
+                let active = shared.touch(range_id, Some(next_counter));
+                if shared.finished(config.target_level).is_some() {
+                    shared.done_message()
+                } else if active {
+                    CoordinatorMessage::Continue
+                } else {
+                    CoordinatorMessage::Revoked
+                }
+            }
+            WorkerMessage::Hit { range_id, counter, level, hashes } => {
+                shared.state.hashes_checked = shared.state.hashes_checked.saturating_add(hashes);
+                let actual = verify_security_level(&shared.state.public_key, counter);
+                if actual != level {
+                    shared.emit(CoordinatorEvent::HitRejected {
+                        worker: session.worker.clone(),
+                        counter,
+                        claimed: level,
+                        actual,
+                    });
+                    // Nothing this worker reported for the range can be trusted
+                    if let Some(assignment) = shared.assignments.remove(&range_id) {
+                        shared.flag(assignment, FlagReason::WrongLevel { counter, claimed: level, actual });
+                    }
+                    CoordinatorMessage::Revoked
+                } else {
+                    if level > shared.state.best_level {
+                        shared.state.best_level = level;
+                        shared.state.best_counter = counter;
+                        shared.emit(CoordinatorEvent::NewLevel {
+                            worker: session.worker.clone(),
+                            counter,
+                            level,
+                            hashes_checked: shared.state.hashes_checked,
+                        });
+                    }
+                    let active = shared.touch(range_id, None);
+                    if shared.finished(config.target_level).is_some() {
+                        shared.done_message()
+                    } else if active {
+                        CoordinatorMessage::Continue
+                    } else {
+                        CoordinatorMessage::Revoked
+                    }
+                }
+            }
+            WorkerMessage::Complete { range_id, hashes, evidence, highest_level } => {
+                shared.state.hashes_checked = shared.state.hashes_checked.saturating_add(hashes);
+                let checked = shared.record_evidence(range_id, None, &evidence, config)
+                    .and_then(|()| shared.check_completed(range_id, highest_level, config));
+                if let Some(assignment) = shared.assignments.remove(&range_id) {
+                    match checked {
+                        Ok(()) => {
+                            shared.state.completed.push(assignment.range);
+                            merge_ranges(&mut shared.state.completed);
+                            shared.emit(CoordinatorEvent::RangeCompleted { worker: assignment.worker, range: assignment.range });
+                        }
+                        Err(reason) => shared.flag(assignment, reason),
+                    }
+                }
+                session.range_id = None;
+                shared.assign(&session.worker, config)
+            }
+        };
+
+        match &reply {
+            CoordinatorMessage::Assign { range_id, .. } => session.range_id = Some(*range_id),
+            CoordinatorMessage::Revoked | CoordinatorMessage::Done { .. } => session.range_id = None,
+            _ => {}
+        }
+
+        self.save(&mut shared);
+        reply
+    }
+
+    /// Serve one worker until it disconnects, errors or times out
+    fn serve(&self, stream: TcpStream) {
+        let mut session = Session { worker: String::from("unknown"), range_id: None };
+
+        let result = (|| -> Result<(), DistributedError> {
+            stream.set_nonblocking(false)?;
+            stream.set_read_timeout(Some(self.config.worker_timeout))?;
+            stream.set_write_timeout(Some(self.config.worker_timeout))?;
+            let mut reader = BufReader::new(stream.try_clone()?);
+            let mut writer = stream;
+
+            while let Some(message) = receive::<WorkerMessage>(&mut reader)? {
+                let reply = self.handle(&mut session, message);
+                send(&mut writer, &reply)?;
+            }
+            Ok(())
+        })();
+        // Disconnects, timeouts and garbage all end the same way: the range goes back
+        let _ = result;
+
+        let mut shared = self.lock();
+        if let Some(range_id) = session.range_id {
+            shared.release(range_id, &self.config);
+            self.save(&mut shared);
+        }
+        shared.connections -= 1;
+    }
+}
+
+/// TCP coordinator for a distributed counter search
+pub struct Coordinator {
+    listener: TcpListener,
+    hub: Arc<Hub>,
+    stop_token: StopToken,
+}
+
+impl Coordinator {
+    /// Listen on `addr`, continuing from `config.state_path` if that file exists
+    pub fn bind<A: ToSocketAddrs>(addr: A, config: CoordinatorConfig) -> Result<Self, DistributedError> {
+        let mut state = match &config.state_path {
+            Some(path) if path.exists() => {
+                let mut state = CoordinatorState::load(path)?;
+                if state.public_key != config.public_key {
+                    return Err(DistributedError::PublicKeyMismatch);
+                }
+                // Nobody holds these ranges after a restart
+                state.pending.append(&mut state.assigned);
+                state.pending.sort();
+                state
+            }
+            _ => CoordinatorState::new(&config),
+        };
+        state.target_level = config.target_level;
+
+        let listener = TcpListener::bind(addr)?;
+
+        Ok(Self {
+            listener,
+            hub: Arc::new(Hub {
+                config,
+                shared: Mutex::new(Shared {
+                    state,
+                    assignments: HashMap::new(),
+                    flagged: HashMap::new(),
+                    sampler: Sampler::new(),
+                    next_range_id: 0,
+                    connections: 0,
+                    save_error: None,
+                    on_event: Box::new(|_| {}),
+                }),
+            }),
+            stop_token: StopToken::new(),
+        })
+    }
+
+    /// Address the coordinator is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr, DistributedError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Use `stop_token` to stop the coordinator from elsewhere
+    pub fn set_stop_token(&mut self, stop_token: StopToken) {
+        self.stop_token = stop_token;
+    }
+
+    /// Call `on_event` for everything that happens (called from connection threads)
+    pub fn set_event_handler<F: Fn(&CoordinatorEvent) + Send + 'static>(&mut self, on_event: F) {
+        self.hub.lock().on_event = Box::new(on_event);
+    }
+
+    /// Current state, including ranges held by workers
+    pub fn state(&self) -> CoordinatorState {
+        self.hub.lock().snapshot()
+    }
+
+    /// Accept workers until the target is reached, the counters run out or the stop token is cancelled
+    pub fn run(&self) -> Result<SearchOutcome, DistributedError> {
+        self.listener.set_nonblocking(true)?;
+        let config = &self.hub.config;
+        let start_time = Instant::now();
+        let start_hashes = self.hub.lock().state.hashes_checked;
+        let mut last_status = Instant::now();
+
+        {
+            let mut shared = self.hub.lock();
+            self.hub.save(&mut shared);
+        }
+
+        let outcome = loop {
+            {
+                let mut shared = self.hub.lock();
+                if let Some(e) = shared.save_error.take() {
+                    return Err(e);
+                }
+                if let Some(outcome) = shared.finished(config.target_level) {
+                    break outcome;
+                }
+                shared.expire_stale(config);
+
+                if last_status.elapsed() >= STATUS_INTERVAL {
+                    last_status = Instant::now();
+                    let elapsed = start_time.elapsed().as_secs_f64();
+                    shared.emit(CoordinatorEvent::Status {
+                        workers: shared.connections,
+                        best_level: shared.state.best_level,
+                        best_counter: shared.state.best_counter,
+                        hashes_checked: shared.state.hashes_checked,
+                        hashes_per_sec: (shared.state.hashes_checked - start_hashes) as f64 / elapsed,
+                    });
+                }
+            }
+
+            if self.stop_token.is_cancelled() {
+                let mut shared = self.hub.lock();
+                self.hub.save(&mut shared);
+                return match shared.save_error.take() {
+                    Some(e) => Err(e),
+                    None => Ok(SearchOutcome::Cancelled),
+                };
+            }
+
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    self.hub.lock().connections += 1;
+                    let hub = Arc::clone(&self.hub);
+                    thread::spawn(move || hub.serve(stream));
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        // Give connected workers the chance to pick up their `done` reply
+        let deadline = Instant::now() + config.worker_timeout;
+        while self.hub.lock().connections > 0 && Instant::now() < deadline && !self.stop_token.is_cancelled() {
+            thread::sleep(POLL_INTERVAL);
+        }
+
+        let mut shared = self.hub.lock();
+        self.hub.save(&mut shared);
+        match shared.save_error.take() {
+            Some(e) => Err(e),
+            None => Ok(outcome),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{temp_test_dir, TEST_PUBLIC_KEY};
+    use crate::distributed::Worker;
+    use crate::hashers::CpuHasher;
+    use std::io::BufRead;
+
+    fn temp_state(name: &str) -> PathBuf {
+        temp_test_dir(&format!("coordinator-{}", name)).join("coordinator.state")
+    }
+
+    /// Run `count` CPU workers against `addr` on their own threads
+    fn spawn_workers(addr: SocketAddr, count: usize) -> Vec<thread::JoinHandle<crate::distributed::WorkerSummary>> {
+        (0..count)
+            .map(|i| {
+                thread::spawn(move || {
+                    let mut worker = Worker::with_batch_size(&format!("cpu-{}", i), CpuHasher, 50);
+                    worker.set_progress_interval(Duration::from_millis(20));
+                    worker.run(addr).unwrap()
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_workers_reach_target() {
+        let path = temp_state("target");
+        let mut config = CoordinatorConfig::new(TEST_PUBLIC_KEY, 14, 14);
+        config.range_size = 300;
+        config.worker_timeout = Duration::from_secs(5);
+        config.evidence_level = 4;
+        config.state_path = Some(path.clone());
+
+        let mut coordinator = Coordinator::bind("127.0.0.1:0", config).unwrap();
+        let flagged = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&flagged);
+        coordinator.set_event_handler(move |event| {
+            if let CoordinatorEvent::RangeFlagged { reason, .. } = event {
+                log.lock().unwrap().push(reason.clone());
+            }
+        });
+        let workers = spawn_workers(coordinator.local_addr().unwrap(), 3);
+
+        let outcome = coordinator.run().unwrap();
+        let SearchOutcome::TargetReached(best) = outcome else {
+            panic!("unexpected outcome {:?}", outcome);
+        };
+        assert!(best.level >= 14);
+        assert_eq!(verify_security_level(TEST_PUBLIC_KEY, best.counter), best.level);
+
+        let summaries: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert!(summaries.iter().all(|s| s.best == Some(best.clone())));
+        assert!(summaries.iter().map(|s| s.hashes_checked).sum::<u64>() > 0);
+        // Honest workers pass every spot check
+        assert_eq!(*flagged.lock().unwrap(), Vec::new());
+
+        // The persisted state agrees with the result
+        let state = CoordinatorState::load(&path).unwrap();
+        assert_eq!(state.best(), best);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_silent_worker_range_reassigned() {
+        let path = temp_state("reassign");
+        let start = u64::MAX - 1000;
+        let mut config = CoordinatorConfig::new(TEST_PUBLIC_KEY, start, 64);
+        config.range_size = 250;
+        config.worker_timeout = Duration::from_millis(300);
+        config.state_path = Some(path.clone());
+
+        let coordinator = Coordinator::bind("127.0.0.1:0", config).unwrap();
+        let addr = coordinator.local_addr().unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        let mut coordinator = coordinator;
+        coordinator.set_event_handler(move |event| log.lock().unwrap().push(event.clone()));
+
+        // A worker that takes the first range and then never says anything again
+        let mut silent = TcpStream::connect(addr).unwrap();
+        send(&mut silent, &WorkerMessage::Hello { worker: "silent".to_string(), hasher: "none".to_string() }).unwrap();
+        let coordinator = thread::spawn(move || {
+            let outcome = coordinator.run().unwrap();
+            (outcome, coordinator.state())
+        });
+        let mut reader = BufReader::new(silent.try_clone().unwrap());
+        let silent_range = match receive::<CoordinatorMessage>(&mut reader).unwrap() {
+            Some(CoordinatorMessage::Assign { start, end, .. }) => CounterRange::new(start, end),
+            other => panic!("unexpected reply {:?}", other),
+        };
+        assert_eq!(silent_range, CounterRange::new(start + 1, start + 250));
+
+        let workers = spawn_workers(addr, 2);
+        let (outcome, state) = coordinator.join().unwrap();
+        assert_eq!(outcome, SearchOutcome::Exhausted);
+
+        // Every counter after the start was checked, including the silent worker's range
+        assert_eq!(state.completed, vec![CounterRange::new(start + 1, u64::MAX)]);
+        assert!(state.pending.is_empty() && state.assigned.is_empty());
+        assert_eq!(state.next_counter, None);
+        assert_eq!(state.hashes_checked, 1000);
+
+        let summaries: Vec<_> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(summaries.iter().map(|s| s.hashes_checked).sum::<u64>(), 1000);
+
+        let events = events.lock().unwrap();
+        assert!(events.contains(&CoordinatorEvent::RangeReleased { worker: "silent".to_string(), range: silent_range }));
+        assert!(events.iter().any(|e| matches!(e,
+            CoordinatorEvent::RangeCompleted { worker, range } if *range == silent_range && worker != "silent")));
+
+        // The silent worker finds out it lost its range
+        let progress = WorkerMessage::Progress { range_id: 0, next_counter: start + 10, hashes: 0, evidence: Vec::new() };
+        send(&mut silent, &progress).unwrap();
+        let mut line = String::new();
+        assert!(reader.read_line(&mut line).map(|n| n == 0).unwrap_or(true));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_rejects_false_hit() {
+        let mut config = CoordinatorConfig::new(TEST_PUBLIC_KEY, 14, 20);
+        config.range_size = 1000;
+
+        let coordinator = Coordinator::bind("127.0.0.1:0", config).unwrap();
+        let mut stream = TcpStream::connect(coordinator.local_addr().unwrap()).unwrap();
+        let stop_token = StopToken::new();
+        let mut coordinator = coordinator;
+        coordinator.set_stop_token(stop_token.clone());
+        let coordinator = thread::spawn(move || {
+            let outcome = coordinator.run().unwrap();
+            (outcome, coordinator.state())
+        });
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut exchange = |message: WorkerMessage| {
+            send(&mut stream, &message).unwrap();
+            receive::<CoordinatorMessage>(&mut reader).unwrap().unwrap()
+        };
+
+        let CoordinatorMessage::Assign { range_id, .. } = exchange(WorkerMessage::Hello {
+            worker: "liar".to_string(),
+            hasher: "none".to_string(),
+        }) else {
+            panic!("expected an assignment");
+        };
+        // Counter 672 really has level 12, 201 only has level 9
+        assert_eq!(exchange(WorkerMessage::Hit { range_id, counter: 672, level: 12, hashes: 700 }), CoordinatorMessage::Continue);
+        assert_eq!(exchange(WorkerMessage::Hit { range_id, counter: 201, level: 19, hashes: 0 }), CoordinatorMessage::Revoked);
+
+        stop_token.cancel();
+        let (outcome, state) = coordinator.join().unwrap();
+        assert_eq!(outcome, SearchOutcome::Cancelled);
+        assert_eq!(state.best(), LevelSearchResult { counter: 672, level: 12 });
+        assert_eq!(state.hashes_checked, 700);
+        assert_eq!(state.pending, vec![CounterRange::new(15, 1014)]);
+    }
+
+    #[test]
+    fn test_flags_untrusted_worker() {
+        let mut config = CoordinatorConfig::new(TEST_PUBLIC_KEY, 14, 30);
+        config.range_size = 20_000;
+        config.evidence_level = 8;
+
+        let mut coordinator = Coordinator::bind("127.0.0.1:0", config).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        coordinator.set_event_handler(move |event| log.lock().unwrap().push(event.clone()));
+        let stop_token = StopToken::new();
+        coordinator.set_stop_token(stop_token.clone());
+        let mut stream = TcpStream::connect(coordinator.local_addr().unwrap()).unwrap();
+        let coordinator = thread::spawn(move || {
+            coordinator.run().unwrap();
+            coordinator.state()
+        });
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut exchange = |message: WorkerMessage| {
+            send(&mut stream, &message).unwrap();
+            receive::<CoordinatorMessage>(&mut reader).unwrap().unwrap()
+        };
+        let assigned = |reply: CoordinatorMessage| match reply {
+            CoordinatorMessage::Assign { range_id, start, end, evidence_level, .. } => {
+                assert_eq!(evidence_level, 8);
+                (range_id, CounterRange::new(start, end))
+            }
+            other => panic!("expected an assignment, got {:?}", other),
+        };
+
+        // Claims the whole range without a single one of the ~78 expected evidence hits
+        let (first_id, first) = assigned(exchange(WorkerMessage::Hello {
+            worker: "lazy".to_string(),
+            hasher: "none".to_string(),
+        }));
+        let reply = exchange(WorkerMessage::Complete { range_id: first_id, hashes: 20_000, evidence: Vec::new(), highest_level: 7 });
+        // The flagged range is not handed back to the same worker
+        let (second_id, second) = assigned(reply);
+        assert_eq!(second, CounterRange::new(first.end + 1, first.end + 20_000));
+
+        // Made-up evidence fails the re-hashing (every hit is sampled in a report this small)
+        let progress = WorkerMessage::Progress {
+            range_id: second_id,
+            next_counter: second.start + 100,
+            hashes: 100,
+            evidence: vec![(second.start + 1, 9)],
+        };
+        assert_eq!(exchange(progress), CoordinatorMessage::Revoked);
+
+        stop_token.cancel();
+        let state = coordinator.join().unwrap();
+        assert_eq!(state.pending, vec![first, second]);
+        assert!(state.completed.is_empty());
+
+        let reasons: Vec<_> = events.lock().unwrap().iter()
+            .filter_map(|event| match event {
+                CoordinatorEvent::RangeFlagged { worker, range, reason } if worker == "lazy" => Some((*range, reason.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reasons.len(), 2);
+        assert!(matches!(reasons[0], (range, FlagReason::Implausible { observed: 0, .. }) if range == first));
+        let actual = verify_security_level(TEST_PUBLIC_KEY, second.start + 1);
+        assert_eq!(reasons[1], (second, FlagReason::WrongLevel { counter: second.start + 1, claimed: 9, actual }));
+    }
+
+    #[test]
+    fn test_ignores_reports_for_other_ranges() {
+        let mut config = CoordinatorConfig::new(TEST_PUBLIC_KEY, 14, 30);
+        config.range_size = 1000;
+
+        let mut coordinator = Coordinator::bind("127.0.0.1:0", config).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&events);
+        coordinator.set_event_handler(move |event| log.lock().unwrap().push(event.clone()));
+        let stop_token = StopToken::new();
+        coordinator.set_stop_token(stop_token.clone());
+        let addr = coordinator.local_addr().unwrap();
+        let coordinator = thread::spawn(move || {
+            coordinator.run().unwrap();
+            coordinator.state()
+        });
+
+        let connect = |name: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            send(&mut stream, &WorkerMessage::Hello { worker: name.to_string(), hasher: "none".to_string() }).unwrap();
+            let Some(CoordinatorMessage::Assign { range_id, start, end, .. }) = receive(&mut reader).unwrap() else {
+                panic!("expected an assignment");
+            };
+            (stream, reader, range_id, CounterRange::new(start, end))
+        };
+        let exchange = |stream: &mut TcpStream, reader: &mut BufReader<TcpStream>, message: WorkerMessage| {
+            send(stream, &message).unwrap();
+            receive::<CoordinatorMessage>(reader).unwrap().unwrap()
+        };
+
+        let (mut owner, mut owner_reader, range_id, range) = connect("owner");
+        let (mut intruder, mut intruder_reader, _, other_range) = connect("intruder");
+
+        // The intruder can neither get the owner's range flagged nor move its progress
+        let false_hit = WorkerMessage::Hit { range_id, counter: 201, level: 19, hashes: 0 };
+        assert_eq!(exchange(&mut intruder, &mut intruder_reader, false_hit), CoordinatorMessage::Revoked);
+        let progress = WorkerMessage::Progress { range_id, next_counter: range.end, hashes: 0, evidence: Vec::new() };
+        assert_eq!(exchange(&mut intruder, &mut intruder_reader, progress), CoordinatorMessage::Revoked);
+
+        // The owner carries on undisturbed
+        let progress = WorkerMessage::Progress { range_id, next_counter: range.start + 10, hashes: 10, evidence: Vec::new() };
+        assert_eq!(exchange(&mut owner, &mut owner_reader, progress), CoordinatorMessage::Continue);
+
+        stop_token.cancel();
+        let state = coordinator.join().unwrap();
+        assert_eq!(state.assigned, vec![CounterRange::new(range.start + 10, range.end), other_range]);
+        assert_eq!(state.best_level, 8);
+        assert!(!events.lock().unwrap().iter().any(|e| matches!(e,
+            CoordinatorEvent::RangeFlagged { .. } | CoordinatorEvent::HitRejected { .. })));
+    }
+
+    #[test]
+    fn test_state_roundtrip() {
+        let path = temp_state("roundtrip");
+        let state = CoordinatorState {
+            public_key: TEST_PUBLIC_KEY.to_string(),
+            target_level: 30,
+            next_counter: Some(5001),
+            pending: vec![CounterRange::new(2500, 3000)],
+            assigned: vec![CounterRange::new(3500, 4000), CounterRange::new(4700, 5000)],
+            completed: vec![CounterRange::new(15, 2499), CounterRange::new(3001, 3499)],
+            best_level: 14,
+            best_counter: 5560,
+            hashes_checked: 3_284,
+        };
+        state.save(&path).unwrap();
+        assert_eq!(CoordinatorState::load(&path).unwrap(), state);
+
+        let exhausted = CoordinatorState { next_counter: None, ..state.clone() };
+        exhausted.save(&path).unwrap();
+        assert_eq!(CoordinatorState::load(&path).unwrap(), exhausted);
+
+        // A restarted coordinator hands the ranges held by workers out again
+        state.save(&path).unwrap();
+        let mut config = CoordinatorConfig::new(TEST_PUBLIC_KEY, 14, 30);
+        config.state_path = Some(path.clone());
+        let coordinator = Coordinator::bind("127.0.0.1:0", config.clone()).unwrap();
+        let restarted = coordinator.state();
+        assert_eq!(restarted.pending, vec![
+            CounterRange::new(2500, 3000),
+            CounterRange::new(3500, 4000),
+            CounterRange::new(4700, 5000),
+        ]);
+        assert!(restarted.assigned.is_empty());
+        assert_eq!(restarted.completed, state.completed);
+        drop(coordinator);
+
+        config.public_key = "c29tZSBvdGhlciBrZXk=".to_string();
+        assert!(matches!(Coordinator::bind("127.0.0.1:0", config), Err(DistributedError::PublicKeyMismatch)));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+//! Spot checks for workers that are not fully trusted
+//!
+//! Besides hits, workers report every counter of a range that reaches the lower
+//! *evidence level* `k`. Each counter has such a level with probability exactly
+//! `2^-k`, so the number of evidence hits in `n` checked counters follows a Poisson
+//! distribution with mean `n / 2^k`. A worker that skipped part of its range reports
+//! too few of them; one that made some up fails the re-hashing of a random sample.
+//! Either way the range is flagged and searched again by someone else.
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+
+/// Default evidence level (one evidence hit per 4,096 counters)
+pub const DEFAULT_EVIDENCE_LEVEL: u8 = 12;
+
+/// Default number of evidence hits re-hashed per report
+pub const DEFAULT_SAMPLE_SIZE: usize = 4;
+
+/// Evidence counts less likely than this for an honest worker are flagged
+pub const IMPLAUSIBLE_PROBABILITY: f64 = 1e-6;
+
+/// Why a range was sent back for rescanning
+#[derive(Debug, Clone, PartialEq)]
+pub enum FlagReason {
+    /// A reported counter does not have the level the worker claimed
+    WrongLevel { counter: u64, claimed: u8, actual: u8 },
+    /// Evidence outside the reported counters, out of order or below the evidence level
+    MalformedEvidence,
+    /// The claimed highest level of the range does not match the evidence
+    HighestLevelMismatch { claimed: u8, evidence: Option<u8> },
+    /// The number of evidence hits is too far from what `counters` hashes produce
+    Implausible { counters: u64, expected: f64, observed: u64, probability: f64 },
+}
+
+impl fmt::Display for FlagReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagReason::WrongLevel { counter, claimed, actual } => {
+                write!(f, "counter {} has level {}, not {}", counter, actual, claimed)
+            }
+            FlagReason::MalformedEvidence => write!(f, "malformed evidence"),
+            FlagReason::HighestLevelMismatch { claimed, evidence: Some(level) } => {
+                write!(f, "claimed highest level {} but the evidence reaches {}", claimed, level)
+            }
+            FlagReason::HighestLevelMismatch { claimed, evidence: None } => {
+                write!(f, "claimed highest level {} without any evidence", claimed)
+            }
+            FlagReason::Implausible { counters, expected, observed, probability } => {
+                write!(f, "{} evidence hits in {} counters, {:.1} expected (p = {:.1e})",
+                       observed, counters, expected, probability)
+            }
+        }
+    }
+}
+
+/// Expected number of evidence hits among `counters` hashes
+pub fn expected_evidence(counters: u64, level: u8) -> f64 {
+    counters as f64 / 2f64.powi(level as i32)
+}
+
+/// `ln(n!)`, exact for small `n` and by Stirling's series above
+fn ln_factorial(n: u64) -> f64 {
+    if n < 32 {
+        return (2..=n).map(|i| (i as f64).ln()).sum();
+    }
+    let n = n as f64;
+    n * n.ln() - n + 0.5 * (2.0 * std::f64::consts::PI * n).ln() + 1.0 / (12.0 * n) - 1.0 / (360.0 * n.powi(3))
+}
+
+/// Probability that a Poisson count with mean `expected` lies at least as far out as `observed`
+///
+/// One-sided, towards whichever tail `observed` is in; close to 0.5 for typical counts.
+pub fn poisson_tail(observed: u64, expected: f64) -> f64 {
+    if expected <= 0.0 {
+        return if observed == 0 { 1.0 } else { 0.0 };
+    }
+
+    let ln_pmf = observed as f64 * expected.ln() - expected - ln_factorial(observed);
+    let pmf = ln_pmf.exp();
+    if pmf == 0.0 {
+        return 0.0;
+    }
+
+    // Walk away from `observed` in both directions until the terms stop mattering
+    let mut lower = pmf;
+    let mut term = pmf;
+    let mut k = observed;
+    while k > 0 {
+        term *= k as f64 / expected;
+        k -= 1;
+        lower += term;
+        if term < lower * f64::EPSILON && (k as f64) < expected {
+            break;
+        }
+    }
+
+    let mut upper = pmf;
+    let mut term = pmf;
+    let mut k = observed;
+    loop {
+        k += 1;
+        term *= expected / k as f64;
+        upper += term;
+        if term < upper * f64::EPSILON && (k as f64) > expected {
+            break;
+        }
+    }
+
+    lower.min(upper).min(1.0)
+}
+
+/// Check that `observed` evidence hits are plausible for `counters` checked counters
+pub fn check_evidence_count(counters: u64, observed: u64, level: u8) -> Result<(), FlagReason> {
+    let expected = expected_evidence(counters, level);
+    let probability = poisson_tail(observed, expected);
+    if probability < IMPLAUSIBLE_PROBABILITY {
+        return Err(FlagReason::Implausible { counters, expected, observed, probability });
+    }
+    Ok(())
+}
+
+/// Picks the evidence hits to re-hash, seeded randomly so workers cannot predict them
+pub(crate) struct Sampler {
+    state: u64,
+}
+
+impl Sampler {
+    pub(crate) fn new() -> Self {
+        Self { state: RandomState::new().hash_one(0u64) }
+    }
+
+    /// SplitMix64
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Up to `count` distinct indices below `len`, in ascending order
+    pub(crate) fn pick(&mut self, len: usize, count: usize) -> Vec<usize> {
+        if len <= count {
+            return (0..len).collect();
+        }
+        let mut picked = Vec::with_capacity(count);
+        while picked.len() < count {
+            let index = (self.next() % len as u64) as usize;
+            if !picked.contains(&index) {
+                picked.push(index);
+            }
+        }
+        picked.sort_unstable();
+        picked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+    use crate::level_improver::SecurityLevelHasher;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() <= expected * 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_poisson_tail() {
+        assert_close(poisson_tail(0, 1.0), (-1.0f64).exp());
+        assert_close(poisson_tail(7, 15.0), 0.018002193);
+        assert_close(poisson_tail(23, 15.0), 0.032744245);
+        assert_eq!(poisson_tail(0, 0.0), 1.0);
+        assert_eq!(poisson_tail(1, 0.0), 0.0);
+
+        // Large means behave like a normal distribution: 5 standard deviations out
+        let tail = poisson_tail(9_500, 10_000.0);
+        assert!(tail > 1e-7 && tail < IMPLAUSIBLE_PROBABILITY, "{}", tail);
+        assert!(poisson_tail(10_000, 10_000.0) > 0.49);
+        assert_eq!(poisson_tail(0, 244_140.625), 0.0);
+    }
+
+    #[test]
+    fn test_evidence_of_real_scan() {
+        let counters: Vec<u64> = (0..100_000).collect();
+        let levels = CpuHasher.calculate_levels_batch(TEST_PUBLIC_KEY, &counters);
+        let evidence = levels.iter().filter(|&&level| level >= 8).count() as u64;
+
+        assert_eq!(check_evidence_count(100_000, evidence, 8), Ok(()));
+        // A worker that only searched half of the range
+        assert!(matches!(check_evidence_count(100_000, evidence / 2, 8), Err(FlagReason::Implausible { .. })));
+        // Or one that claims far more than the hashes can produce
+        assert!(check_evidence_count(100_000, evidence * 2, 8).is_err());
+    }
+
+    #[test]
+    fn test_sampler() {
+        let mut sampler = Sampler::new();
+        assert_eq!(sampler.pick(3, 4), vec![0, 1, 2]);
+        for _ in 0..100 {
+            let picked = sampler.pick(10, 4);
+            assert_eq!(picked.len(), 4);
+            assert!(picked.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(picked.iter().all(|&index| index < 10));
+        }
+    }
+}
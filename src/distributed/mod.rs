@@ -0,0 +1,232 @@
+//! Distributed counter search: a coordinator hands out ranges, workers search them
+//!
+//! The coordinator owns one public key (omega) and splits the counters after the
+//! identity's current counter into disjoint ranges. Workers connect over TCP with
+//! any [`SecurityLevelHasher`](crate::SecurityLevelHasher), search the ranges they
+//! are given and stream back progress and hits. Ranges of workers that disconnect or
+//! stay silent for longer than the timeout go back into the pool, starting at the
+//! last counter the worker reported. Only the public key travels over the wire.
+//!
+//! The protocol is newline-delimited JSON. The worker speaks first and the
+//! coordinator answers every message with exactly one reply. `progress`, `hit` and
+//! `complete` carry the number of hashes computed since the previous report.
+//! `progress` and `complete` also carry the evidence for the counters they cover:
+//! every counter at or above the assignment's evidence level (see [`evidence`]).
+//!
+//! | Worker sends | Coordinator replies             |
+//! |--------------|---------------------------------|
+//! | `hello`      | `assign`, `wait` or `done`      |
+//! | `request`    | `assign`, `wait` or `done`      |
+//! | `progress`   | `continue`, `revoked` or `done` |
+//! | `hit`        | `continue`, `revoked` or `done` |
+//! | `complete`   | `assign`, `wait` or `done`      |
+
+pub mod coordinator;
+pub mod evidence;
+pub mod worker;
+
+pub use coordinator::{Coordinator, CoordinatorConfig, CoordinatorEvent};
+pub use worker::{Worker, WorkerEvent};
+#[allow(unused_imports)] // Public API type
+pub use coordinator::CoordinatorState;
+#[allow(unused_imports)] // Public API type
+pub use evidence::FlagReason;
+#[allow(unused_imports)] // Public API type
+pub use worker::WorkerSummary;
+
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DistributedError {
+    #[error("Network error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Protocol error: {0}")]
+    ProtocolError(String),
+
+    #[error("Failed to parse coordinator state: {0}")]
+    IniError(String),
+
+    #[error("Missing [Coordinator] section in state file")]
+    MissingSection,
+
+    #[error("Missing '{0}' key in [Coordinator] section")]
+    MissingKey(&'static str),
+
+    #[error("Invalid value for '{0}': {1}")]
+    InvalidValue(&'static str, String),
+
+    #[error("Coordinator state was created for a different public key")]
+    PublicKeyMismatch,
+}
+
+/// Inclusive range of counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct CounterRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl CounterRange {
+    pub fn new(start: u64, end: u64) -> Self {
+        Self { start, end }
+    }
+
+    /// Number of counters in the range
+    pub fn count(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    #[allow(dead_code)] // Public API method
+    pub fn contains(&self, counter: u64) -> bool {
+        (self.start..=self.end).contains(&counter)
+    }
+}
+
+impl fmt::Display for CounterRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+impl FromStr for CounterRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.trim().split_once('-').ok_or_else(|| format!("invalid range '{}'", s))?;
+        let start = start.parse::<u64>().map_err(|e| format!("invalid range '{}': {}", s, e))?;
+        let end = end.parse::<u64>().map_err(|e| format!("invalid range '{}': {}", s, e))?;
+        if start > end {
+            return Err(format!("invalid range '{}': start after end", s));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// Sort ranges and merge overlapping or adjacent ones
+pub fn merge_ranges(ranges: &mut Vec<CounterRange>) {
+    ranges.sort();
+    let mut merged: Vec<CounterRange> = Vec::with_capacity(ranges.len());
+    for range in ranges.drain(..) {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    *ranges = merged;
+}
+
+/// Messages from a worker to the coordinator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerMessage {
+    /// First message after connecting
+    Hello { worker: String, hasher: String },
+    /// Ask for work again after a `wait`
+    Request,
+    /// Everything before `next_counter` in the range has been checked
+    Progress { range_id: u64, next_counter: u64, hashes: u64, evidence: Vec<(u64, u8)> },
+    /// A counter better than the best level known when the range was assigned
+    Hit { range_id: u64, counter: u64, level: u8, hashes: u64 },
+    /// The whole range has been checked; `highest_level` is the best level seen in it
+    Complete { range_id: u64, hashes: u64, evidence: Vec<(u64, u8)>, highest_level: u8 },
+}
+
+/// Replies from the coordinator to a worker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CoordinatorMessage {
+    /// Search `start..=end`, report hits above `best_level` and evidence from `evidence_level`
+    Assign {
+        range_id: u64,
+        public_key: String,
+        start: u64,
+        end: u64,
+        best_level: u8,
+        target_level: u8,
+        evidence_level: u8,
+    },
+    /// No range is free right now; send `request` after this many milliseconds
+    Wait { millis: u64 },
+    /// Keep going with the current range
+    Continue,
+    /// The range was handed to another worker; stop it and send `request`
+    Revoked,
+    /// The search is over (target reached or counters exhausted)
+    Done { best_counter: u64, best_level: u8 },
+}
+
+/// Write one message as a JSON line
+pub(crate) fn send<T: Serialize>(writer: &mut impl Write, message: &T) -> Result<(), DistributedError> {
+    let mut line = serde_json::to_string(message)
+        .map_err(|e| DistributedError::ProtocolError(e.to_string()))?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read one JSON line message, `None` once the peer has closed the connection
+pub(crate) fn receive<T: DeserializeOwned>(reader: &mut impl BufRead) -> Result<Option<T>, DistributedError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| DistributedError::ProtocolError(format!("{}: {}", e, line.trim())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_ranges() {
+        let mut ranges = vec![
+            CounterRange::new(200, 299),
+            CounterRange::new(0, 99),
+            CounterRange::new(100, 199),
+            CounterRange::new(500, 599),
+            CounterRange::new(550, 650),
+        ];
+        merge_ranges(&mut ranges);
+        assert_eq!(ranges, vec![CounterRange::new(0, 299), CounterRange::new(500, 650)]);
+
+        let mut ranges = vec![CounterRange::new(u64::MAX - 1, u64::MAX), CounterRange::new(10, u64::MAX - 2)];
+        merge_ranges(&mut ranges);
+        assert_eq!(ranges, vec![CounterRange::new(10, u64::MAX)]);
+    }
+
+    #[test]
+    fn test_range_parse() {
+        assert_eq!("15-114".parse(), Ok(CounterRange::new(15, 114)));
+        assert_eq!(CounterRange::new(15, 114).to_string(), "15-114");
+        assert_eq!(CounterRange::new(15, 114).count(), 100);
+        assert!("20-10".parse::<CounterRange>().is_err());
+        assert!("20".parse::<CounterRange>().is_err());
+    }
+
+    #[test]
+    fn test_message_json() {
+        let message = WorkerMessage::Hit { range_id: 3, counter: 672, level: 12, hashes: 700 };
+        let mut buffer = Vec::new();
+        send(&mut buffer, &message).unwrap();
+        assert_eq!(String::from_utf8(buffer.clone()).unwrap(), "{\"type\":\"hit\",\"range_id\":3,\"counter\":672,\"level\":12,\"hashes\":700}\n");
+
+        let mut reader = buffer.as_slice();
+        assert_eq!(receive::<WorkerMessage>(&mut reader).unwrap(), Some(message));
+        assert_eq!(receive::<WorkerMessage>(&mut reader).unwrap(), None);
+
+        // Evidence travels as [counter, level] pairs
+        let message = WorkerMessage::Complete { range_id: 3, hashes: 100, evidence: vec![(672, 12)], highest_level: 12 };
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(json, "{\"type\":\"complete\",\"range_id\":3,\"hashes\":100,\"evidence\":[[672,12]],\"highest_level\":12}");
+        assert_eq!(serde_json::from_str::<WorkerMessage>(&json).unwrap(), message);
+    }
+}
@@ -0,0 +1,311 @@
+//! Worker side of the distributed search
+//!
+//! A worker connects to a coordinator, searches the ranges it is assigned with its
+//! [`SecurityLevelHasher`] and reports progress at a fixed interval, so the coordinator
+//! knows it is alive and how far it got. Every counter better than the best level
+//! known so far is reported as a hit right away; counters at or above the evidence
+//! level travel with the next report so the coordinator can spot-check the work.
+
+use std::io::BufReader;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{receive, send, CoordinatorMessage, CounterRange, DistributedError, WorkerMessage};
+use crate::level_improver::{counter_batch, LevelSearchResult, SecurityLevelHasher, StopToken, DEFAULT_BATCH_SIZE};
+
+/// Default time between progress reports
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Something that happened on a worker
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorkerEvent {
+    /// The coordinator handed out a range
+    RangeAssigned { range: CounterRange },
+    /// A counter better than the best known level was found and reported
+    Hit { counter: u64, level: u8, hashes_checked: u64 },
+    /// The whole range was checked
+    RangeCompleted { range: CounterRange, hashes: u64, elapsed_secs: f64 },
+    /// The coordinator gave the range to someone else
+    RangeRevoked { range: CounterRange },
+}
+
+/// What a worker did before it stopped
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WorkerSummary {
+    /// Number of ranges checked completely
+    pub ranges_completed: u64,
+    /// Hashes computed by this worker
+    pub hashes_checked: u64,
+    /// Hits reported to the coordinator
+    pub hits: u64,
+    /// Final result announced by the coordinator, `None` if the worker was stopped first
+    pub best: Option<LevelSearchResult>,
+}
+
+/// Line-based connection to the coordinator
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn exchange(&mut self, message: &WorkerMessage) -> Result<CoordinatorMessage, DistributedError> {
+        send(&mut self.writer, message)?;
+        receive(&mut self.reader)?
+            .ok_or_else(|| DistributedError::ProtocolError("coordinator closed the connection".to_string()))
+    }
+}
+
+/// Client that searches ranges handed out by a [`Coordinator`](super::Coordinator)
+pub struct Worker<H: SecurityLevelHasher> {
+    name: String,
+    hasher: H,
+    batch_size: usize,
+    progress_interval: Duration,
+    stop_token: StopToken,
+    on_event: Box<dyn FnMut(&WorkerEvent) + Send>,
+}
+
+impl<H: SecurityLevelHasher> Worker<H> {
+    /// Worker called `name` with default batch size
+    #[allow(dead_code)] // Public API method
+    pub fn new(name: &str, hasher: H) -> Self {
+        Self::with_batch_size(name, hasher, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Worker called `name` with a custom batch size
+    pub fn with_batch_size(name: &str, hasher: H, batch_size: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            hasher,
+            batch_size,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            stop_token: StopToken::new(),
+            on_event: Box::new(|_| {}),
+        }
+    }
+
+    /// Report progress this often (must stay well below the coordinator's timeout)
+    #[allow(dead_code)] // Public API method
+    pub fn set_progress_interval(&mut self, interval: Duration) {
+        self.progress_interval = interval;
+    }
+
+    /// Use `stop_token` to stop the worker from elsewhere
+    pub fn set_stop_token(&mut self, stop_token: StopToken) {
+        self.stop_token = stop_token;
+    }
+
+    /// Call `on_event` for assigned, completed and revoked ranges and for hits
+    pub fn set_event_handler<F: FnMut(&WorkerEvent) + Send + 'static>(&mut self, on_event: F) {
+        self.on_event = Box::new(on_event);
+    }
+
+    /// Work for the coordinator at `addr` until it reports the search as done
+    ///
+    /// When the stop token is cancelled the worker reports how far it got and
+    /// disconnects; the coordinator reassigns the rest of its range.
+    pub fn run<A: ToSocketAddrs>(&mut self, addr: A) -> Result<WorkerSummary, DistributedError> {
+        let stream = TcpStream::connect(addr)?;
+        let mut connection = Connection {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        };
+        let mut summary = WorkerSummary::default();
+
+        let mut reply = connection.exchange(&WorkerMessage::Hello {
+            worker: self.name.clone(),
+            hasher: self.hasher.name().to_string(),
+        })?;
+
+        loop {
+            reply = match reply {
+                CoordinatorMessage::Assign { range_id, public_key, start, end, best_level, evidence_level, .. } => {
+                    let range = CounterRange::new(start, end);
+                    (self.on_event)(&WorkerEvent::RangeAssigned { range });
+                    let reply = self.search_range(
+                        &mut connection,
+                        &mut summary,
+                        range_id,
+                        &public_key,
+                        range,
+                        best_level,
+                        evidence_level,
+                    )?;
+                    match reply {
+                        Some(CoordinatorMessage::Revoked) => {
+                            (self.on_event)(&WorkerEvent::RangeRevoked { range });
+                            connection.exchange(&WorkerMessage::Request)?
+                        }
+                        Some(reply) => reply,
+                        None => return Ok(summary),
+                    }
+                }
+                CoordinatorMessage::Wait { millis } => {
+                    let until = Instant::now() + Duration::from_millis(millis);
+                    while Instant::now() < until {
+                        if self.stop_token.is_cancelled() {
+                            return Ok(summary);
+                        }
+                        thread::sleep(Duration::from_millis(millis.min(50)));
+                    }
+                    connection.exchange(&WorkerMessage::Request)?
+                }
+                CoordinatorMessage::Continue | CoordinatorMessage::Revoked => {
+                    connection.exchange(&WorkerMessage::Request)?
+                }
+                CoordinatorMessage::Done { best_counter, best_level } => {
+                    summary.best = Some(LevelSearchResult { counter: best_counter, level: best_level });
+                    return Ok(summary);
+                }
+            };
+        }
+    }
+
+    /// Search one range, returning the coordinator's reply that ends it (`None` when stopped)
+    #[allow(clippy::too_many_arguments)] // Mirrors the assignment
+    fn search_range(
+        &mut self,
+        connection: &mut Connection,
+        summary: &mut WorkerSummary,
+        range_id: u64,
+        public_key: &str,
+        range: CounterRange,
+        mut best_level: u8,
+        evidence_level: u8,
+    ) -> Result<Option<CoordinatorMessage>, DistributedError> {
+        let start_time = Instant::now();
+        let mut last_report = Instant::now();
+        let mut next = Some(range.start);
+        let mut range_hashes = 0u64;
+        let mut unreported = 0u64;
+        let mut evidence = Vec::new();
+        let mut highest_level = 0u8;
+
+        while let Some(batch_start) = next {
+            if self.stop_token.is_cancelled() {
+                // Tell the coordinator how far we got; the reply does not matter anymore
+                let _ = connection.exchange(&WorkerMessage::Progress {
+                    range_id,
+                    next_counter: batch_start,
+                    hashes: unreported,
+                    evidence,
+                });
+                return Ok(None);
+            }
+
+            // Prepare batch of counters to check, stopping at the end of the range
+            let (batch, following) = counter_batch(batch_start, self.batch_size);
+            let last = (*batch.end()).min(range.end);
+            next = if last == range.end { None } else { following };
+
+            let counters: Vec<u64> = (batch_start..=last).collect();
+            let levels = self.hasher.calculate_levels_batch(public_key, &counters);
+            range_hashes += counters.len() as u64;
+            unreported += counters.len() as u64;
+            summary.hashes_checked += counters.len() as u64;
+
+            for (&counter, &level) in counters.iter().zip(&levels) {
+                highest_level = highest_level.max(level);
+                if level >= evidence_level {
+                    evidence.push((counter, level));
+                }
+                if level <= best_level {
+                    continue;
+                }
+                best_level = level;
+                summary.hits += 1;
+                (self.on_event)(&WorkerEvent::Hit { counter, level, hashes_checked: summary.hashes_checked });
+
+                let hit = WorkerMessage::Hit { range_id, counter, level, hashes: unreported };
+                unreported = 0;
+                match connection.exchange(&hit)? {
+                    CoordinatorMessage::Continue => {}
+                    reply => return Ok(Some(reply)),
+                }
+            }
+
+            if let Some(next_counter) = next
+                && last_report.elapsed() >= self.progress_interval
+            {
+                let reply = connection.exchange(&WorkerMessage::Progress {
+                    range_id,
+                    next_counter,
+                    hashes: unreported,
+                    evidence: std::mem::take(&mut evidence),
+                })?;
+                unreported = 0;
+                last_report = Instant::now();
+                if reply != CoordinatorMessage::Continue {
+                    return Ok(Some(reply));
+                }
+            }
+        }
+
+        summary.ranges_completed += 1;
+        (self.on_event)(&WorkerEvent::RangeCompleted {
+            range,
+            hashes: range_hashes,
+            elapsed_secs: start_time.elapsed().as_secs_f64(),
+        });
+        connection.exchange(&WorkerMessage::Complete { range_id, hashes: unreported, evidence, highest_level })
+            .map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_worker_protocol() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let worker = thread::spawn(move || {
+            let mut worker = Worker::with_batch_size("test", CpuHasher, 16);
+            worker.run(addr).unwrap()
+        });
+
+        // Play the coordinator with a scripted conversation
+        let (stream, _) = listener.accept().unwrap();
+        let mut connection = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut expect = |expected: WorkerMessage, reply: CoordinatorMessage| {
+            assert_eq!(receive::<WorkerMessage>(&mut connection).unwrap(), Some(expected));
+            send(&mut writer, &reply).unwrap();
+        };
+
+        expect(
+            WorkerMessage::Hello { worker: "test".to_string(), hasher: CpuHasher.name().to_string() },
+            CoordinatorMessage::Assign {
+                range_id: 7,
+                public_key: TEST_PUBLIC_KEY.to_string(),
+                start: 600,
+                end: 700,
+                best_level: 9,
+                target_level: 30,
+                evidence_level: 10,
+            },
+        );
+        // 672 is the only counter above level 9 in 600..=700, and the only one from level 10 up
+        expect(WorkerMessage::Hit { range_id: 7, counter: 672, level: 12, hashes: 80 }, CoordinatorMessage::Continue);
+        expect(
+            WorkerMessage::Complete { range_id: 7, hashes: 21, evidence: vec![(672, 12)], highest_level: 12 },
+            CoordinatorMessage::Wait { millis: 10 },
+        );
+        expect(WorkerMessage::Request, CoordinatorMessage::Done { best_counter: 672, best_level: 12 });
+
+        let summary = worker.join().unwrap();
+        assert_eq!(summary, WorkerSummary {
+            ranges_completed: 1,
+            hashes_checked: 101,
+            hits: 1,
+            best: Some(LevelSearchResult { counter: 672, level: 12 }),
+        });
+    }
+}
@@ -0,0 +1,184 @@
+//! Time and probability estimates for reaching a security level
+//!
+//! Every hash independently reaches level `L` or higher with probability `p = 2^-L`,
+//! so the number of hashes until the first hit follows a geometric distribution:
+//! `P(hit within n hashes) = 1 - (1 - p)^n`. The current level does not matter for
+//! the remaining work (the search has no memory); it only decides whether any work is left.
+
+use std::time::{Duration, Instant};
+use crate::level_improver::SecurityLevelHasher;
+
+/// Probability that a single hash reaches `level` or higher
+pub fn hit_probability(level: u8) -> f64 {
+    0.5_f64.powi(level as i32)
+}
+
+/// Expected number of hashes until `level` is reached (`2^level`)
+pub fn expected_hashes(level: u8) -> f64 {
+    2_f64.powi(level as i32)
+}
+
+/// Probability that `level` has been reached within `hashes` hashes
+pub fn success_probability(level: u8, hashes: f64) -> f64 {
+    let p = hit_probability(level);
+    // 1 - (1 - p)^n, computed with ln_1p/exp_m1 so tiny probabilities don't round to 0
+    -(hashes * (-p).ln_1p()).exp_m1()
+}
+
+/// Number of hashes after which `level` has been reached with the given probability
+pub fn hashes_for_probability(level: u8, probability: f64) -> f64 {
+    let p = hit_probability(level);
+    (-probability).ln_1p() / (-p).ln_1p()
+}
+
+/// Expected work and time to go from one level to another at a fixed hashrate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Estimate {
+    pub current_level: u8,
+    pub target_level: u8,
+    pub hashes_per_sec: f64,
+    /// Mean number of hashes (0 if the target is already reached)
+    pub expected_hashes: f64,
+    /// Hashes after which the target is reached with 50%, 90% and 99% probability
+    pub median_hashes: f64,
+    pub p90_hashes: f64,
+    pub p99_hashes: f64,
+}
+
+impl Estimate {
+    pub fn new(current_level: u8, target_level: u8, hashes_per_sec: f64) -> Self {
+        let reached = current_level >= target_level;
+        let hashes = |probability: f64| {
+            if reached { 0.0 } else { hashes_for_probability(target_level, probability) }
+        };
+
+        Self {
+            current_level,
+            target_level,
+            hashes_per_sec,
+            expected_hashes: if reached { 0.0 } else { expected_hashes(target_level) },
+            median_hashes: hashes(0.5),
+            p90_hashes: hashes(0.9),
+            p99_hashes: hashes(0.99),
+        }
+    }
+
+    /// Seconds needed for `hashes` hashes at this estimate's hashrate
+    pub fn secs(&self, hashes: f64) -> f64 {
+        if hashes == 0.0 {
+            0.0
+        } else {
+            hashes / self.hashes_per_sec
+        }
+    }
+}
+
+/// How a running search compares with the distribution of its target level
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetOdds {
+    pub target_level: u8,
+    /// Expected time until the target is hit at the current hashrate. The search has
+    /// no memory, so this stays at `2^target / hashrate` however long it has been running.
+    pub eta_secs: f64,
+    /// Probability that the target would have been hit within the hashes checked so far
+    pub probability: f64,
+    /// Hashes checked relative to the expected `2^target` (above 1: unlucky so far)
+    pub luck: f64,
+}
+
+impl TargetOdds {
+    pub fn new(target_level: u8, hashes_checked: u64, hashes_per_sec: f64) -> Self {
+        let expected = expected_hashes(target_level);
+        Self {
+            target_level,
+            eta_secs: if hashes_per_sec > 0.0 { expected / hashes_per_sec } else { f64::INFINITY },
+            probability: success_probability(target_level, hashes_checked as f64),
+            luck: hashes_checked as f64 / expected,
+        }
+    }
+}
+
+/// Measure a hasher's throughput by hashing batches of `public_key` for about `duration`
+///
+/// The first batch is run before the clock starts, so one-time setup (e.g. CUDA
+/// kernel compilation and device allocation) does not count.
+pub fn calibrate<H: SecurityLevelHasher + ?Sized>(
+    hasher: &H,
+    public_key: &str,
+    batch_size: usize,
+    duration: Duration,
+) -> f64 {
+    let batch_size = batch_size.max(1) as u64;
+    let counters: Vec<u64> = (0..batch_size).collect();
+    hasher.calculate_levels_batch(public_key, &counters);
+
+    let start = Instant::now();
+    let mut hashes = 0u64;
+    let mut next_counter = batch_size;
+    while hashes == 0 || start.elapsed() < duration {
+        let counters: Vec<u64> = (next_counter..next_counter + batch_size).collect();
+        hasher.calculate_levels_batch(public_key, &counters);
+        hashes += batch_size;
+        next_counter += batch_size;
+    }
+
+    hashes as f64 / start.elapsed().as_secs_f64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() <= expected * 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_geometric_quantiles() {
+        let estimate = Estimate::new(8, 30, 1e9);
+        let mean = 2_f64.powi(30);
+
+        assert_eq!(estimate.expected_hashes, mean);
+        // For small p the quantiles approach -ln(1 - q) * 2^L
+        assert_close(estimate.median_hashes, std::f64::consts::LN_2 * mean);
+        assert_close(estimate.p90_hashes, 10_f64.ln() * mean);
+        assert_close(estimate.p99_hashes, 100_f64.ln() * mean);
+        assert_close(estimate.secs(estimate.expected_hashes), mean / 1e9);
+    }
+
+    #[test]
+    fn test_success_probability() {
+        // After the expected number of hashes the chance is 1 - 1/e, not 50%
+        assert_close(success_probability(30, expected_hashes(30)), 1.0 - (-1_f64).exp());
+        assert_close(success_probability(40, hashes_for_probability(40, 0.9)), 0.9);
+        assert_eq!(success_probability(1, 1.0), 0.5);
+        assert_eq!(success_probability(20, 0.0), 0.0);
+        assert!(success_probability(100, 1.0) > 0.0);
+    }
+
+    #[test]
+    fn test_target_odds() {
+        let odds = TargetOdds::new(20, 1 << 21, 1e6);
+        assert_close(odds.eta_secs, 2_f64.powi(20) / 1e6);
+        assert_close(odds.probability, 1.0 - (-2_f64).exp());
+        assert_eq!(odds.luck, 2.0);
+
+        assert_eq!(TargetOdds::new(20, 0, 0.0).eta_secs, f64::INFINITY);
+    }
+
+    #[test]
+    fn test_target_already_reached() {
+        let estimate = Estimate::new(24, 20, 1e6);
+        assert_eq!(estimate.expected_hashes, 0.0);
+        assert_eq!(estimate.p99_hashes, 0.0);
+        assert_eq!(estimate.secs(estimate.p99_hashes), 0.0);
+    }
+
+    #[test]
+    fn test_calibrate() {
+        let hashes_per_sec = calibrate(&CpuHasher, TEST_PUBLIC_KEY, 1000, Duration::from_millis(50));
+        assert!(hashes_per_sec > 0.0 && hashes_per_sec.is_finite());
+    }
+}
@@ -0,0 +1,178 @@
+//! CPU hasher that reuses the SHA-1 state of the constant public key prefix
+//!
+//! TS3 public keys are ~108 bytes long, so the first 64-byte SHA-1 block of
+//! `public_key || counter` never changes during a search. This hasher compresses
+//! that prefix once per batch and only runs the final one or two compression
+//! rounds for each counter (the same split the CUDA kernel uses).
+//!
+//! The prefix state is exported by [`sha1_core`](crate::hashers::sha1_core), the
+//! same implementation that prepares the CUDA blocks. The per-counter tail blocks
+//! go through the `sha1` crate's compression, which uses the SHA extensions when
+//! the CPU has them and is about twice as fast as the portable rounds.
+
+use rayon::prelude::*;
+use crate::hashers::sha1_core::{self, Sha1};
+use crate::level_improver::SecurityLevelHasher;
+use crate::helpers::{count_trailing_zero_bits, format_counter};
+
+/// Maximum suffix length supported by [`Sha1Midstate::finalize_with`]
+const MAX_SUFFIX_LEN: usize = 55; // 63 tail bytes + 55 + padding still fit in two blocks
+
+/// SHA-1 state after absorbing all complete 64-byte blocks of a message prefix
+#[derive(Debug, Clone)]
+pub struct Sha1Midstate {
+    state: [u32; 5],
+    tail: [u8; 64],   // Prefix bytes after the last complete block
+    tail_len: usize,
+    prefix_len: usize,
+}
+
+impl Sha1Midstate {
+    /// Compress all complete blocks of `prefix` and keep the remainder
+    pub fn new(prefix: &[u8]) -> Self {
+        let mut sha1 = Sha1::new();
+        sha1.update(prefix);
+
+        let mut tail = [0u8; 64];
+        let tail_len = sha1.buffered().len();
+        tail[..tail_len].copy_from_slice(sha1.buffered());
+
+        Self {
+            state: sha1.state(),
+            tail,
+            tail_len,
+            prefix_len: prefix.len(),
+        }
+    }
+
+    /// SHA-1 state after the complete prefix blocks
+    pub fn state(&self) -> [u32; 5] {
+        self.state
+    }
+
+    /// Build the padded final block(s) for `prefix || suffix`
+    ///
+    /// Returns the block bytes and the number of 64-byte blocks used (1 or 2).
+    ///
+    /// # Panics
+    /// Panics if `suffix` is longer than 55 bytes.
+    pub fn padded_tail(&self, suffix: &[u8]) -> ([u8; 128], usize) {
+        assert!(suffix.len() <= MAX_SUFFIX_LEN, "suffix too long for midstate finalization");
+
+        // Remaining message + 0x80 + 8-byte length always fits in two blocks
+        let mut buffer = [0u8; 128];
+        let remaining = self.tail_len + suffix.len();
+        buffer[..self.tail_len].copy_from_slice(&self.tail[..self.tail_len]);
+        buffer[self.tail_len..remaining].copy_from_slice(suffix);
+        buffer[remaining] = 0x80;
+
+        let blocks = if remaining + 1 + 8 <= 64 { 1 } else { 2 };
+        let end = blocks * 64;
+        let bit_len = ((self.prefix_len + suffix.len()) as u64) * 8;
+        buffer[end - 8..end].copy_from_slice(&bit_len.to_be_bytes());
+
+        (buffer, blocks)
+    }
+
+    /// Compute SHA-1(prefix || suffix) from the saved state
+    ///
+    /// # Panics
+    /// Panics if `suffix` is longer than 55 bytes.
+    pub fn finalize_with(&self, suffix: &[u8]) -> [u8; 20] {
+        let (buffer, blocks) = self.padded_tail(suffix);
+
+        let mut state = self.state;
+        for block in buffer[..blocks * 64].chunks_exact(64) {
+            let block: &[u8; 64] = block.try_into().expect("chunks_exact yields 64-byte blocks");
+            sha1::compress(&mut state, &[(*block).into()]);
+        }
+        sha1_core::state_bytes(&state)
+    }
+
+    /// Security level of `prefix || counter`
+    pub fn security_level(&self, counter: u64) -> u8 {
+        let mut digits = [0u8; 20];
+        let hash = self.finalize_with(format_counter(counter, &mut digits));
+        count_trailing_zero_bits(&hash)
+    }
+}
+
+/// CPU hasher that precomputes the SHA-1 midstate of the public key
+pub struct CpuMidstateHasher;
+
+impl SecurityLevelHasher for CpuMidstateHasher {
+    fn calculate_level(&self, public_key: &str, counter: u64) -> u8 {
+        Sha1Midstate::new(public_key.as_bytes()).security_level(counter)
+    }
+
+    /// Batch processing: one midstate per batch, counters spread across CPU cores
+    fn calculate_levels_batch(&self, public_key: &str, counters: &[u64]) -> Vec<u8> {
+        let midstate = Sha1Midstate::new(public_key.as_bytes());
+        counters.par_iter()
+            .map(|&counter| midstate.security_level(counter))
+            .collect()
+    }
+
+    fn name(&self) -> &str {
+        "CPU (midstate)"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+
+    #[test]
+    fn test_calculate_level() {
+        let hasher = CpuMidstateHasher;
+        assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 14), 8);
+        assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 201), 9);
+        assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 672), 12);
+    }
+
+    #[test]
+    fn test_matches_cpu_hasher() {
+        // Cover single- and two-block tails for short, medium and TS3-length prefixes
+        let counters: Vec<u64> = (0..2000)
+            .chain([9_999_999_999, 10_000_000_000, 9_999_999_999_999_999, 10_000_000_000_000_000, u64::MAX])
+            .collect();
+
+        for public_key in ["abc", "0123456789012345678901234567890123456789012345678901", TEST_PUBLIC_KEY] {
+            let expected = CpuHasher.calculate_levels_batch(public_key, &counters);
+            let levels = CpuMidstateHasher.calculate_levels_batch(public_key, &counters);
+            assert_eq!(levels, expected, "Mismatch for public key {}", public_key);
+        }
+    }
+
+    #[test]
+    fn test_finalize_with_matches_sha1() {
+        use sha1::{Digest, Sha1};
+
+        let message = [b'1'; 200];
+        for split in [0, 1, 55, 56, 63, 64, 65, 127, 128, 150] {
+            let suffix_len = (message.len() - split).min(MAX_SUFFIX_LEN);
+            let (prefix, suffix) = message[..split + suffix_len].split_at(split);
+            let expected: [u8; 20] = Sha1::digest(&message[..split + suffix_len]).into();
+            assert_eq!(Sha1Midstate::new(prefix).finalize_with(suffix), expected, "split {}", split);
+        }
+    }
+
+    #[test]
+    fn test_state_matches_sha1_core() {
+        // The TS3 key's first 64 bytes form the only complete prefix block
+        let mut state = sha1_core::INIT_STATE;
+        sha1_core::compress_bytes(&mut state, TEST_PUBLIC_KEY.as_bytes()[..64].try_into().unwrap());
+        assert_eq!(Sha1Midstate::new(TEST_PUBLIC_KEY.as_bytes()).state(), state);
+
+        let suffix = b"18446744073709551615";
+        let message = [TEST_PUBLIC_KEY.as_bytes(), suffix].concat();
+        assert_eq!(Sha1Midstate::new(TEST_PUBLIC_KEY.as_bytes()).finalize_with(suffix), sha1_core::digest(&message));
+    }
+
+    #[test]
+    fn test_hasher_name() {
+        assert_eq!(CpuMidstateHasher.name(), "CPU (midstate)");
+    }
+}
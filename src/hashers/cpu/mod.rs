@@ -1,5 +1,13 @@
 //! CPU-based SHA-1 hasher implementation for TS3 security level calculation
 
+pub mod midstate;
+pub mod shani;
+pub mod simd;
+
+pub use midstate::CpuMidstateHasher;
+pub use shani::CpuShaNiHasher;
+pub use simd::CpuSimdHasher;
+
 use sha1::{Digest, Sha1};
 use rayon::prelude::*;
 use crate::level_improver::SecurityLevelHasher;
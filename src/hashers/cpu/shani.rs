@@ -0,0 +1,110 @@
+//! SHA-NI reporting CPU hasher for TS3 security level calculation
+//!
+//! The `sha1` crate already compresses with the x86 SHA extensions (`sha1rnds4`,
+//! `sha1nexte`, `sha1msg1`, `sha1msg2`) when the running CPU has them, and with its
+//! portable rounds otherwise. `cpu`, `cpu-midstate` and this hasher all go through
+//! it, so they share the same compression path. This hasher runs the midstate
+//! search and detects which path the crate takes, so `name()` tells benchmark
+//! numbers on SHA-NI machines apart from those on machines without it.
+
+use crate::level_improver::SecurityLevelHasher;
+use super::midstate::{CpuMidstateHasher, Sha1Midstate};
+
+/// Midstate CPU hasher that reports whether the SHA extensions are in use
+pub struct CpuShaNiHasher {
+    sha_ni: bool,
+}
+
+impl CpuShaNiHasher {
+    /// Create a hasher, detecting SHA-NI support of the running CPU
+    pub fn new() -> Self {
+        Self { sha_ni: Self::is_supported() }
+    }
+
+    /// Whether the `sha1` crate uses the SHA extensions on the running CPU
+    ///
+    /// Checks the same CPU features the crate's x86 backend detects at runtime.
+    pub fn is_supported() -> bool {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            std::is_x86_feature_detected!("sha")
+                && std::is_x86_feature_detected!("sse2")
+                && std::is_x86_feature_detected!("ssse3")
+                && std::is_x86_feature_detected!("sse4.1")
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        {
+            false
+        }
+    }
+
+    /// Whether this hasher uses the SHA-NI code path
+    #[allow(dead_code)] // Public API method
+    pub fn uses_sha_ni(&self) -> bool {
+        self.sha_ni
+    }
+}
+
+impl Default for CpuShaNiHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SecurityLevelHasher for CpuShaNiHasher {
+    fn calculate_level(&self, public_key: &str, counter: u64) -> u8 {
+        Sha1Midstate::new(public_key.as_bytes()).security_level(counter)
+    }
+
+    /// Batch processing: one midstate per batch, counters spread across CPU cores
+    fn calculate_levels_batch(&self, public_key: &str, counters: &[u64]) -> Vec<u8> {
+        CpuMidstateHasher.calculate_levels_batch(public_key, counters)
+    }
+
+    fn name(&self) -> &str {
+        if self.sha_ni {
+            "CPU SHA-NI"
+        } else {
+            "CPU SHA-NI (unsupported, portable fallback)"
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+
+    #[test]
+    fn test_calculate_level() {
+        let hasher = CpuShaNiHasher::new();
+        assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 14), 8);
+        assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 201), 9);
+        assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 672), 12);
+    }
+
+    #[test]
+    fn test_matches_cpu_hasher() {
+        let counters: Vec<u64> = (0..2000)
+            .chain([9_999_999_999_999_999, 10_000_000_000_000_000, u64::MAX])
+            .collect();
+
+        let hasher = CpuShaNiHasher::new();
+        for public_key in ["abc", "0123456789012345678901234567890123456789", TEST_PUBLIC_KEY] {
+            let expected = CpuHasher.calculate_levels_batch(public_key, &counters);
+            let levels = hasher.calculate_levels_batch(public_key, &counters);
+            assert_eq!(levels, expected, "{} mismatch for public key {}", hasher.name(), public_key);
+        }
+    }
+
+    #[test]
+    fn test_hasher_name() {
+        let hasher = CpuShaNiHasher::new();
+        if CpuShaNiHasher::is_supported() {
+            assert_eq!(hasher.name(), "CPU SHA-NI");
+        } else {
+            assert_eq!(hasher.name(), "CPU SHA-NI (unsupported, portable fallback)");
+        }
+    }
+}
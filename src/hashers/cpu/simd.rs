@@ -0,0 +1,343 @@
+//! Multi-lane SIMD SHA-1 hasher for TS3 security level calculation
+//!
+//! Hashes 4 (SSE2), 8 (AVX2) or 16 (AVX-512) counters at once. Every SHA-1
+//! word is stored as one array with a value per lane, so each round operates
+//! on all lanes together; the round functions are compiled once per instruction
+//! set with `#[target_feature]` and picked at runtime. Like
+//! [`CpuMidstateHasher`](super::CpuMidstateHasher), the constant public key
+//! prefix is compressed once per batch and only the tail blocks are hashed
+//! per counter.
+
+use rayon::prelude::*;
+use crate::level_improver::SecurityLevelHasher;
+use crate::helpers::{count_trailing_zero_bits, format_counter};
+use super::midstate::Sha1Midstate;
+
+/// Number of counters handed to one rayon task
+const CHUNK_SIZE: usize = 4096;
+
+/// Instruction set used for the SHA-1 lanes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    /// One counter at a time (no SIMD available)
+    Scalar,
+    /// 4 lanes in 128-bit registers
+    Sse2,
+    /// 8 lanes in 256-bit registers
+    Avx2,
+    /// 16 lanes in 512-bit registers
+    Avx512,
+}
+
+impl SimdLevel {
+    /// Detect the widest instruction set supported by the running CPU
+    pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if std::is_x86_feature_detected!("avx512f") {
+                return SimdLevel::Avx512;
+            }
+            if std::is_x86_feature_detected!("avx2") {
+                return SimdLevel::Avx2;
+            }
+            if std::is_x86_feature_detected!("sse2") {
+                return SimdLevel::Sse2;
+            }
+        }
+        SimdLevel::Scalar
+    }
+
+    /// Whether the running CPU supports this level
+    pub fn is_supported(self) -> bool {
+        match self {
+            SimdLevel::Scalar => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Sse2 => std::is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx2 => std::is_x86_feature_detected!("avx2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            SimdLevel::Avx512 => std::is_x86_feature_detected!("avx512f"),
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            _ => false,
+        }
+    }
+
+    /// Number of counters hashed at once
+    #[allow(dead_code)] // Public API method
+    pub fn lanes(self) -> usize {
+        match self {
+            SimdLevel::Scalar => 1,
+            SimdLevel::Sse2 => 4,
+            SimdLevel::Avx2 => 8,
+            SimdLevel::Avx512 => 16,
+        }
+    }
+}
+
+/// CPU hasher computing several counters per instruction in SIMD lanes
+pub struct CpuSimdHasher {
+    level: SimdLevel,
+}
+
+impl CpuSimdHasher {
+    /// Create a hasher using the widest instruction set of the running CPU
+    pub fn new() -> Self {
+        Self { level: SimdLevel::detect() }
+    }
+
+    /// Create a hasher for a specific instruction set
+    ///
+    /// Returns `None` if the running CPU does not support it.
+    #[allow(dead_code)] // Public API method
+    pub fn with_level(level: SimdLevel) -> Option<Self> {
+        level.is_supported().then_some(Self { level })
+    }
+
+    /// Instruction set used by this hasher
+    #[allow(dead_code)] // Public API method
+    pub fn level(&self) -> SimdLevel {
+        self.level
+    }
+
+    fn hash_chunk(&self, midstate: &Sha1Midstate, counters: &[u64], levels: &mut [u8]) {
+        match self.level {
+            SimdLevel::Scalar => {
+                for (level, &counter) in levels.iter_mut().zip(counters) {
+                    *level = midstate.security_level(counter);
+                }
+            }
+            // SAFETY: `level` is only set to an x86 SIMD variant after runtime
+            // detection confirmed the CPU supports the enabled target features
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            #[allow(unsafe_code)]
+            SimdLevel::Sse2 => unsafe { x86::hash_chunk_sse2(midstate, counters, levels) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            #[allow(unsafe_code)]
+            SimdLevel::Avx2 => unsafe { x86::hash_chunk_avx2(midstate, counters, levels) },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            #[allow(unsafe_code)]
+            SimdLevel::Avx512 => unsafe { x86::hash_chunk_avx512(midstate, counters, levels) },
+            #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+            _ => unreachable!("SIMD levels are only available on x86"),
+        }
+    }
+}
+
+impl Default for CpuSimdHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SecurityLevelHasher for CpuSimdHasher {
+    fn calculate_level(&self, public_key: &str, counter: u64) -> u8 {
+        let mut level = [0u8];
+        self.hash_chunk(&Sha1Midstate::new(public_key.as_bytes()), &[counter], &mut level);
+        level[0]
+    }
+
+    /// Batch processing: one midstate per batch, SIMD lanes within each rayon task
+    fn calculate_levels_batch(&self, public_key: &str, counters: &[u64]) -> Vec<u8> {
+        let midstate = Sha1Midstate::new(public_key.as_bytes());
+        let mut levels = vec![0u8; counters.len()];
+
+        levels.par_chunks_mut(CHUNK_SIZE)
+            .zip(counters.par_chunks(CHUNK_SIZE))
+            .for_each(|(levels, counters)| self.hash_chunk(&midstate, counters, levels));
+
+        levels
+    }
+
+    fn name(&self) -> &str {
+        match self.level {
+            SimdLevel::Scalar => "CPU SIMD (scalar fallback)",
+            SimdLevel::Sse2 => "CPU SIMD (SSE2, 4 lanes)",
+            SimdLevel::Avx2 => "CPU SIMD (AVX2, 8 lanes)",
+            SimdLevel::Avx512 => "CPU SIMD (AVX-512, 16 lanes)",
+        }
+    }
+}
+
+/// Entry points compiled with the matching target features enabled
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    use super::{hash_chunk_lanes, Sha1Midstate};
+
+    #[target_feature(enable = "sse2")]
+    pub fn hash_chunk_sse2(midstate: &Sha1Midstate, counters: &[u64], levels: &mut [u8]) {
+        hash_chunk_lanes::<4>(midstate, counters, levels)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub fn hash_chunk_avx2(midstate: &Sha1Midstate, counters: &[u64], levels: &mut [u8]) {
+        hash_chunk_lanes::<8>(midstate, counters, levels)
+    }
+
+    #[target_feature(enable = "avx512f")]
+    pub fn hash_chunk_avx512(midstate: &Sha1Midstate, counters: &[u64], levels: &mut [u8]) {
+        hash_chunk_lanes::<16>(midstate, counters, levels)
+    }
+}
+
+/// One SHA-1 word per lane
+type Lanes<const N: usize> = [u32; N];
+
+/// Hash `counters` in groups of `N` lanes
+///
+/// Inlined into the `#[target_feature]` entry points so the lane loops are
+/// vectorized for the selected instruction set.
+#[inline(always)]
+fn hash_chunk_lanes<const N: usize>(midstate: &Sha1Midstate, counters: &[u64], levels: &mut [u8]) {
+    let init = midstate.state();
+
+    for (group, out) in counters.chunks(N).zip(levels.chunks_mut(N)) {
+        let mut first = [[0u32; N]; 16];
+        let mut second = [[0u32; N]; 16];
+        let mut two_blocks = [false; N];
+
+        // Build the padded tail blocks; unused lanes of a short group repeat the last counter
+        for lane in 0..N {
+            let counter = group[lane.min(group.len() - 1)];
+            let mut digits = [0u8; 20];
+            let (tail, blocks) = midstate.padded_tail(format_counter(counter, &mut digits));
+
+            for word in 0..16 {
+                first[word][lane] = u32::from_be_bytes(tail[word * 4..word * 4 + 4].try_into().unwrap());
+                second[word][lane] = u32::from_be_bytes(tail[64 + word * 4..64 + word * 4 + 4].try_into().unwrap());
+            }
+            two_blocks[lane] = blocks == 2;
+        }
+
+        let mut state = [[0u32; N]; 5];
+        for (lanes, &value) in state.iter_mut().zip(init.iter()) {
+            *lanes = [value; N];
+        }
+
+        compress_lanes(&mut state, first);
+        let mut result = state;
+        if two_blocks.iter().any(|&two| two) {
+            compress_lanes(&mut result, second);
+        }
+
+        for (lane, level) in out.iter_mut().enumerate() {
+            let words = if two_blocks[lane] { &result } else { &state };
+            let mut hash = [0u8; 20];
+            for (i, word) in words.iter().enumerate() {
+                hash[i * 4..(i + 1) * 4].copy_from_slice(&word[lane].to_be_bytes());
+            }
+            *level = count_trailing_zero_bits(&hash);
+        }
+    }
+}
+
+/// SHA-1 compression of one block per lane
+#[inline(always)]
+fn compress_lanes<const N: usize>(state: &mut [Lanes<N>; 5], mut w: [Lanes<N>; 16]) {
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+
+    macro_rules! round {
+        ($i:expr, $k:expr, $f:expr) => {{
+            let i: usize = $i;
+            if i >= 16 {
+                for l in 0..N {
+                    w[i & 15][l] = (w[(i + 13) & 15][l] ^ w[(i + 8) & 15][l]
+                        ^ w[(i + 2) & 15][l] ^ w[i & 15][l]).rotate_left(1);
+                }
+            }
+            for l in 0..N {
+                let f: u32 = $f(b[l], c[l], d[l]);
+                let temp = a[l].rotate_left(5)
+                    .wrapping_add(f)
+                    .wrapping_add(e[l])
+                    .wrapping_add($k)
+                    .wrapping_add(w[i & 15][l]);
+                e[l] = d[l];
+                d[l] = c[l];
+                c[l] = b[l].rotate_left(30);
+                b[l] = a[l];
+                a[l] = temp;
+            }
+        }};
+    }
+
+    // Rounds 0-19: f = (b & c) | ((~b) & d), k = 0x5A827999
+    for i in 0..20 {
+        round!(i, 0x5A827999, |b: u32, c: u32, d: u32| (b & c) | (!b & d));
+    }
+    // Rounds 20-39: f = b ^ c ^ d, k = 0x6ED9EBA1
+    for i in 20..40 {
+        round!(i, 0x6ED9EBA1, |b: u32, c: u32, d: u32| b ^ c ^ d);
+    }
+    // Rounds 40-59: f = (b & c) | (b & d) | (c & d), k = 0x8F1BBCDC
+    for i in 40..60 {
+        round!(i, 0x8F1BBCDC, |b: u32, c: u32, d: u32| (b & c) | (b & d) | (c & d));
+    }
+    // Rounds 60-79: f = b ^ c ^ d, k = 0xCA62C1D6
+    for i in 60..80 {
+        round!(i, 0xCA62C1D6, |b: u32, c: u32, d: u32| b ^ c ^ d);
+    }
+
+    for (lanes, value) in state.iter_mut().zip([a, b, c, d, e]) {
+        for l in 0..N {
+            lanes[l] = lanes[l].wrapping_add(value[l]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+
+    fn supported_hashers() -> Vec<CpuSimdHasher> {
+        [SimdLevel::Scalar, SimdLevel::Sse2, SimdLevel::Avx2, SimdLevel::Avx512]
+            .into_iter()
+            .filter_map(CpuSimdHasher::with_level)
+            .collect()
+    }
+
+    #[test]
+    fn test_calculate_level() {
+        for hasher in supported_hashers() {
+            // Known test cases from CpuHasher
+            assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 14), 8, "{}", hasher.name());
+            assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 201), 9, "{}", hasher.name());
+            assert_eq!(hasher.calculate_level(TEST_PUBLIC_KEY, 672), 12, "{}", hasher.name());
+        }
+    }
+
+    #[test]
+    fn test_batch_known_levels() {
+        let counters: Vec<u64> = (14..=700).collect();
+        for hasher in supported_hashers() {
+            let levels = hasher.calculate_levels_batch(TEST_PUBLIC_KEY, &counters);
+            assert_eq!(levels[0], 8, "{}", hasher.name());
+            assert_eq!(levels[201 - 14], 9, "{}", hasher.name());
+            assert_eq!(levels[672 - 14], 12, "{}", hasher.name());
+        }
+    }
+
+    #[test]
+    fn test_matches_cpu_hasher() {
+        // Groups mixing one- and two-block tails, digit-length changes and non-consecutive counters
+        let counters: Vec<u64> = (99_990..100_013)
+            .chain([5, 1_000_000_000_000, 3, 9_999_999_999_999_999, 10_000_000_000_000_000, u64::MAX, 42])
+            .chain(0..1000)
+            .collect();
+
+        for public_key in ["abc", "0123456789012345678901234567890123456789", TEST_PUBLIC_KEY] {
+            let expected = CpuHasher.calculate_levels_batch(public_key, &counters);
+            for hasher in supported_hashers() {
+                let levels = hasher.calculate_levels_batch(public_key, &counters);
+                assert_eq!(levels, expected, "{} mismatch for public key {}", hasher.name(), public_key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scalar_always_supported() {
+        assert!(CpuSimdHasher::with_level(SimdLevel::Scalar).is_some());
+        assert_eq!(SimdLevel::Scalar.lanes(), 1);
+    }
+}
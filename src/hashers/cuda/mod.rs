@@ -2,28 +2,20 @@
 //!
 //! This module provides GPU-accelerated hashing using NVIDIA CUDA.
 
+use crate::hashers::sha1_core;
 use crate::level_improver::SecurityLevelHasher;
 use cudarc::driver::*;
 use cudarc::nvrtc::Ptx;
 use std::sync::Arc;
 use std::cell::RefCell;
 
-/// SHA1 initial hash values (RFC 3174)
-const SHA1_INIT_HASH: [u32; 5] = [
-    0x67452301,
-    0xEFCDAB89,
-    0x98BADCFE,
-    0x10325476,
-    0xC3D2E1F0,
-];
-
 /// CUDA-based hasher using GPU acceleration
 pub struct CudaHasher {
     ctx: Arc<CudaContext>,
     module: Arc<CudaModule>,
     module_optimized: Arc<CudaModule>,
     // Cached public key in device memory for optimized batch processing (interior mutability)
-    cached_public_key: RefCell<Option<(CudaSlice<u8>, usize)>>, // (device memory, length)
+    cached_public_key: RefCell<Option<(CudaSlice<u8>, Vec<u8>)>>, // (device memory, host copy)
     // Kernel launch parameters
     threads_per_block: usize,
     dynamic_shared_mem_bytes: Option<usize>,
@@ -75,8 +67,8 @@ impl CudaHasher {
     /// Returns the 20-byte (160-bit) SHA1 hash
     /// Supports multi-block messages of any length
     pub fn hash_message(&self, message: &[u8]) -> Result<[u8; 20], CudaError> {
-        let blocks = prepare_sha1_blocks(message);
-        let mut h = SHA1_INIT_HASH;
+        let blocks = sha1_core::padded_blocks(message);
+        let mut h = sha1_core::INIT_STATE;
 
         // Process each block, chaining the hash values
         for block in blocks {
@@ -84,7 +76,7 @@ impl CudaHasher {
             h.copy_from_slice(&output);
         }
 
-        Ok(words_to_hash_bytes(&h))
+        Ok(sha1_core::state_bytes(&h))
     }
 
     /// Optimized batch computation of security levels using GPU-side counter generation
@@ -138,24 +130,25 @@ impl CudaHasher {
         let stream = self.ctx.default_stream();
 
         // Cache public key in device memory if not already cached or if it changed
+        // (compare the bytes: keys of different identities usually have the same length)
         let public_key_bytes = public_key.as_bytes();
         let mut cache = self.cached_public_key.borrow_mut();
 
         let needs_upload = match &*cache {
             None => true,
-            Some((_, cached_len)) => *cached_len != public_key_bytes.len(),
+            Some((_, cached_key)) => cached_key.as_slice() != public_key_bytes,
         };
 
         if needs_upload {
             let d_public_key = stream.memcpy_stod(public_key_bytes)
                 .map_err(CudaError::MemoryError)?;
-            *cache = Some((d_public_key, public_key_bytes.len()));
+            *cache = Some((d_public_key, public_key_bytes.to_vec()));
         }
 
         // Get the cached public key device memory and clone the reference
         let (d_public_key, public_key_len) = {
-            let (slice, len) = cache.as_ref().unwrap();
-            (slice.clone(), *len)
+            let (slice, key) = cache.as_ref().unwrap();
+            (slice.clone(), key.len())
         };
 
         // Drop the borrow explicitly
@@ -170,7 +163,7 @@ impl CudaHasher {
             .map_err(CudaError::KernelLoadError)?;
 
         // Configure kernel launch
-        let num_blocks = (num_counters + threads_per_block - 1) / threads_per_block;
+        let num_blocks = num_counters.div_ceil(threads_per_block);
 
         // Allocate dynamic shared memory for multi-block path
         // Each thread needs 128 bytes for message buffer (used only when total_len > 55)
@@ -269,16 +262,6 @@ impl SecurityLevelHasher for CudaHasher {
     }
 }
 
-/// Convert 5 u32 words to a 20-byte SHA1 hash (big-endian)
-fn words_to_hash_bytes(words: &[u32; 5]) -> [u8; 20] {
-    let mut hash_bytes = [0u8; 20];
-    for (i, &word) in words.iter().enumerate() {
-        let bytes = word.to_be_bytes();
-        hash_bytes[i * 4..(i + 1) * 4].copy_from_slice(&bytes);
-    }
-    hash_bytes
-}
-
 /// Launch CUDA kernel for a single SHA1 block
 ///
 /// # Safety invariants
@@ -294,7 +277,7 @@ fn launch_single_block_kernel(
 ) -> Result<[u32; 5], CudaError> {
     let stream = ctx.default_stream();
     let sha1_func = module.load_function("sha1_simple")
-        .map_err(|e| CudaError::KernelLoadError(e))?;
+        .map_err(CudaError::KernelLoadError)?;
 
     // Allocate device memory
     let d_input = stream.memcpy_stod(block)
@@ -336,71 +319,6 @@ fn launch_single_block_kernel(
     Ok(result)
 }
 
-/// Prepare a message for SHA1 hashing by splitting it into 64-byte blocks
-/// and applying proper SHA1 padding to the final block.
-/// Returns a vector of blocks, where each block is 16 u32 words (64 bytes)
-fn prepare_sha1_blocks(message: &[u8]) -> Vec<[u32; 16]> {
-    let msg_len = message.len();
-    let bit_len = (msg_len * 8) as u64;
-
-    // Calculate number of blocks needed
-    // Each block is 64 bytes. We need space for: message + 0x80 byte + 8-byte length
-    let blocks_needed = (msg_len + 1 + 8 + 63) / 64;
-
-    let mut blocks = Vec::with_capacity(blocks_needed);
-
-    // Process full 64-byte blocks from the message
-    let mut pos = 0;
-    while pos + 64 <= msg_len {
-        let mut block = [0u32; 16];
-        let chunk = &message[pos..pos + 64];
-
-        // Convert bytes to u32 words (big-endian)
-        for i in 0..16 {
-            let word_bytes = [
-                chunk[i * 4],
-                chunk[i * 4 + 1],
-                chunk[i * 4 + 2],
-                chunk[i * 4 + 3],
-            ];
-            block[i] = u32::from_be_bytes(word_bytes);
-        }
-        blocks.push(block);
-        pos += 64;
-    }
-
-    // Handle the final block(s) with remaining data and padding
-    let remaining = msg_len - pos;
-    let mut final_bytes = vec![0u8; blocks_needed * 64 - pos];
-
-    // Copy remaining message bytes
-    final_bytes[..remaining].copy_from_slice(&message[pos..]);
-
-    // Add the '1' bit (0x80 byte)
-    final_bytes[remaining] = 0x80;
-
-    // Add length in bits at the end (last 8 bytes, big-endian)
-    let len_pos = final_bytes.len() - 8;
-    final_bytes[len_pos..].copy_from_slice(&bit_len.to_be_bytes());
-
-    // Convert remaining bytes to blocks
-    for chunk in final_bytes.chunks(64) {
-        let mut block = [0u32; 16];
-        for i in 0..16 {
-            let word_bytes = [
-                chunk[i * 4],
-                chunk[i * 4 + 1],
-                chunk[i * 4 + 2],
-                chunk[i * 4 + 3],
-            ];
-            block[i] = u32::from_be_bytes(word_bytes);
-        }
-        blocks.push(block);
-    }
-
-    blocks
-}
-
 /// Compile the CUDA kernel from the sha1.cu file
 fn compile_kernel() -> Result<Ptx, CudaError> {
     let kernel_src = include_str!("sha1.cu");
@@ -422,6 +340,7 @@ fn compile_optimized_kernel() -> Result<Ptx, CudaError> {
 
 /// Errors that can occur with CUDA operations
 #[derive(Debug, thiserror::Error)]
+#[allow(clippy::enum_variant_names)] // Public API names
 pub enum CudaError {
     #[error("Failed to initialize CUDA device: {0}")]
     DeviceInitError(#[source] cudarc::driver::DriverError),
@@ -484,10 +403,10 @@ mod tests {
     }
 
     #[test]
-    fn test_prepare_sha1_blocks() {
+    fn test_padded_blocks() {
         // Test the padding function with "abc" (single block)
         let message = b"abc";
-        let blocks = prepare_sha1_blocks(message);
+        let blocks = sha1_core::padded_blocks(message);
 
         assert_eq!(blocks.len(), 1, "Short message should produce 1 block");
         let block = blocks[0];
@@ -497,8 +416,8 @@ mod tests {
         assert_eq!(block[0], 0x61626380);
 
         // Middle words should be zero
-        for i in 1..14 {
-            assert_eq!(block[i], 0, "Padding word {} should be zero", i);
+        for (i, &word) in block.iter().enumerate().take(14).skip(1) {
+            assert_eq!(word, 0, "Padding word {} should be zero", i);
         }
 
         // Last two words should contain the bit length (24 bits = 0x18)
@@ -507,13 +426,25 @@ mod tests {
 
         // Test with a longer message (200 bytes, should produce 4 blocks)
         let long_message = b"111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111";
-        let long_blocks = prepare_sha1_blocks(long_message);
+        let long_blocks = sha1_core::padded_blocks(long_message);
 
         // 200 bytes + 1 byte padding + 8 byte length = 209 bytes
         // 209 / 64 = 3.27, so we need 4 blocks
         assert_eq!(long_blocks.len(), 4, "Long message should produce 4 blocks");
     }
 
+    #[test]
+    fn test_hash_message_matches_sha1_core() {
+        let hasher = CudaHasher::new().expect("Failed to initialize CUDA hasher");
+
+        // Every length around the one-, two- and three-block padding boundaries
+        let message: Vec<u8> = (0..200u32).map(|i| (i * 7 + 3) as u8).collect();
+        for len in 0..=message.len() {
+            let hash = hasher.hash_message(&message[..len]).expect("Failed to hash message");
+            assert_eq!(hash, sha1_core::digest(&message[..len]), "length {}", len);
+        }
+    }
+
     #[test]
     fn test_cuda_matches_cpu_short_message() {
         use crate::hashers::CpuHasher;
@@ -621,8 +552,8 @@ mod tests {
 
         // Debug: print first few results
         println!("First 10 levels:");
-        for i in 0..10 {
-            println!("  Counter {}: level {}", 14 + i, levels[i]);
+        for (i, level) in levels.iter().enumerate().take(10) {
+            println!("  Counter {}: level {}", 14 + i, level);
         }
 
         // Check specific known values
@@ -642,6 +573,42 @@ mod tests {
         assert_eq!(*best.1, 12, "Best counter should have level 12");
     }
 
+    #[test]
+    fn test_optimized_kernel_switches_public_keys() {
+        use crate::hashers::CpuHasher;
+
+        // Two keys of the same length must not share the cached device copy
+        let hasher = CudaHasher::new().expect("Failed to initialize CUDA hasher");
+        let key_a = "ME0DAgcAAgEgAiEAy/hhqSBja7A6FTZG5s+BMnQfCqYyS9sGsbyMKBb7spYCIQCBEtZWrZtewnxuh2hsigJswGHchu3XcaiQDZziMsxTsA==";
+        let key_b = key_a.replace("y", "z");
+        let counters: Vec<u64> = (0..1000).collect();
+
+        for key in [key_a, key_b.as_str(), key_a] {
+            let levels = hasher.calculate_levels_batch(key, &counters);
+            assert_eq!(levels, CpuHasher.calculate_levels_batch(key, &counters), "mismatch for key {}", key);
+        }
+    }
+
+    #[test]
+    fn test_optimized_kernel_counter_digit_boundaries() {
+        use crate::hashers::kernel_reference;
+
+        // Counters of 16, 17 and 20 digits, up to u64::MAX
+        let hasher = CudaHasher::new().expect("Failed to initialize CUDA hasher");
+        let public_key = "ME0DAgcAAgEgAiEAy/hhqSBja7A6FTZG5s+BMnQfCqYyS9sGsbyMKBb7spYCIQCBEtZWrZtewnxuh2hsigJswGHchu3XcaiQDZziMsxTsA==";
+
+        for key in ["abc", "kkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkk", public_key] {
+            for start in [9_999_999_999_999_990, u64::MAX - 19] {
+                let levels = hasher.calculate_levels_optimized(key, start, 20)
+                    .expect("Optimized kernel failed");
+                for (i, &level) in levels.iter().enumerate() {
+                    let counter = start + i as u64;
+                    assert_eq!(level, kernel_reference::security_level(key.as_bytes(), counter), "counter {}", counter);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_both_kernels_match() {
         // Test that old kernel (hash_message) and new optimized kernel produce same results
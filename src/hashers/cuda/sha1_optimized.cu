@@ -7,7 +7,11 @@
 
 #define ROTLEFT(a,b) (((a) << (b)) | ((a) >> (32-(b))))
 
+// Maximum number of decimal digits of a u64 (18446744073709551615)
+#define MAX_COUNTER_DIGITS 20
+
 // Device function: Convert u64 to ASCII string (decimal representation)
+// buffer must hold MAX_COUNTER_DIGITS bytes
 // Returns the number of digits written
 __device__ int u64_to_string(unsigned long long value, unsigned char* buffer) {
     if (value == 0) {
@@ -15,14 +19,12 @@ __device__ int u64_to_string(unsigned long long value, unsigned char* buffer) {
         return 1;
     }
 
-    // Temporary buffer to store digits in reverse order
-    // 16 bytes is enough for counters up to 10^16 (10 quadrillion)
-    // Max u64 needs 20 digits, but practical use cases don't exceed this
-    unsigned char temp[16];
+    // Temporary buffer to store digits in reverse order (covers the full u64 range)
+    unsigned char temp[MAX_COUNTER_DIGITS];
     int count = 0;
 
     // Extract digits one by one
-    while (value > 0 && count < 16) {
+    while (value > 0) {
         temp[count++] = '0' + (value % 10);
         value /= 10;
     }
@@ -174,10 +176,10 @@ extern "C" __global__ void sha1_security_level_optimized(
     // Calculate this thread's counter value
     unsigned long long counter = start_counter + idx;
 
-    // Convert counter to string FIRST (small 16-byte stack buffer)
+    // Convert counter to string FIRST (small stack buffer)
     // This allows us to determine the total message length early
-    // 16 bytes is enough for counters up to 10^16
-    unsigned char counter_str[16];
+    // 20 bytes hold every u64 value, so the GPU agrees with the CPU up to u64::MAX
+    unsigned char counter_str[MAX_COUNTER_DIGITS];
     int counter_len = u64_to_string(counter, counter_str);
 
     int total_len = public_key_len + counter_len;
@@ -314,6 +316,7 @@ extern "C" __global__ void sha1_security_level_optimized(
     // ============ SLOW PATH: MULTI-BLOCK ============
     // For messages > 55 bytes, use dynamic shared memory to avoid stack usage
     // Dynamic shared memory is allocated per-block at kernel launch
+    // 128 bytes per thread fit a 108-byte TS3 key plus a 20-digit counter exactly
     extern __shared__ unsigned char s_message[];
     unsigned char* my_message = &s_message[threadIdx.x * 128];
 
@@ -0,0 +1,224 @@
+//! CPU reference model of the optimized CUDA kernel (`cuda/sha1_optimized.cu`)
+//!
+//! Mirrors step by step how `sha1_security_level_optimized` turns a public key
+//! and counter into SHA-1 blocks: the decimal conversion of `u64_to_string`, the
+//! single-block fast path and the multi-block slow path with its padding. The
+//! kernel's boundary cases (digit count changes, fast/slow path switch, the
+//! 128-byte message slot) can therefore be tested without a GPU.
+
+use super::sha1_core;
+
+/// Maximum number of decimal digits of a u64 (`MAX_COUNTER_DIGITS` in the kernel)
+pub const MAX_COUNTER_DIGITS: usize = 20;
+
+/// Longest message the kernel hashes on its single-block fast path
+pub const FAST_PATH_MAX_LEN: usize = 55;
+
+/// Per-thread dynamic shared memory slot the slow path builds its message in
+pub const MESSAGE_SLOT_LEN: usize = 128;
+
+/// Port of the kernel's `u64_to_string`
+///
+/// Writes the decimal digits of `value` to the start of `buffer` and returns how many were written.
+pub fn u64_to_string(value: u64, buffer: &mut [u8; MAX_COUNTER_DIGITS]) -> usize {
+    if value == 0 {
+        buffer[0] = b'0';
+        return 1;
+    }
+
+    // Digits in reverse order, like the kernel's temp buffer
+    let mut temp = [0u8; MAX_COUNTER_DIGITS];
+    let mut count = 0;
+    let mut value = value;
+    while value > 0 {
+        temp[count] = b'0' + (value % 10) as u8;
+        count += 1;
+        value /= 10;
+    }
+
+    for i in 0..count {
+        buffer[i] = temp[count - 1 - i];
+    }
+    count
+}
+
+/// Whether the kernel takes its single-block fast path for this message
+pub fn uses_fast_path(public_key_len: usize, counter: u64) -> bool {
+    let mut digits = [0u8; MAX_COUNTER_DIGITS];
+    public_key_len + u64_to_string(counter, &mut digits) <= FAST_PATH_MAX_LEN
+}
+
+/// The padded big-endian SHA-1 blocks the kernel hashes for `public_key || counter`
+///
+/// # Panics
+/// Panics if a slow path message does not fit the kernel's 128-byte per-thread slot.
+pub fn message_blocks(public_key: &[u8], counter: u64) -> Vec<[u32; 16]> {
+    let mut counter_str = [0u8; MAX_COUNTER_DIGITS];
+    let counter_len = u64_to_string(counter, &mut counter_str);
+    let total_len = public_key.len() + counter_len;
+    let bit_len = (total_len as u64) * 8;
+
+    // Fast path: key, counter, 0x80 and the length packed straight into w[]
+    if total_len <= FAST_PATH_MAX_LEN {
+        let mut w = [0u32; 16];
+        let message = public_key.iter().chain(&counter_str[..counter_len]);
+        for (pos, &byte) in message.enumerate() {
+            w[pos / 4] |= (byte as u32) << (24 - (pos % 4) * 8);
+        }
+        w[total_len / 4] |= 0x80 << (24 - (total_len % 4) * 8);
+        w[14] = (bit_len >> 32) as u32;
+        w[15] = bit_len as u32;
+        return vec![w];
+    }
+
+    // Slow path: message built in the thread's shared memory slot
+    assert!(total_len <= MESSAGE_SLOT_LEN, "message does not fit the kernel's shared memory slot");
+    let mut my_message = [0u8; MESSAGE_SLOT_LEN];
+    my_message[..public_key.len()].copy_from_slice(public_key);
+    my_message[public_key.len()..total_len].copy_from_slice(&counter_str[..counter_len]);
+
+    let blocks_needed = (total_len + 1 + 8).div_ceil(64);
+    let mut blocks = Vec::with_capacity(blocks_needed);
+
+    // Complete 64-byte blocks
+    let mut pos = 0;
+    while pos + 64 <= total_len {
+        let mut block = [0u32; 16];
+        for (i, word) in block.iter_mut().enumerate() {
+            let start = pos + i * 4;
+            *word = u32::from_be_bytes(my_message[start..start + 4].try_into().unwrap());
+        }
+        blocks.push(block);
+        pos += 64;
+    }
+
+    // Final block(s): remaining bytes and 0x80 in the first, length in the last
+    let remaining = total_len - pos;
+    let final_blocks = blocks_needed - pos / 64;
+    for block_num in 0..final_blocks {
+        let mut block = [0u32; 16];
+        if block_num == 0 {
+            for i in 0..remaining {
+                block[i / 4] |= (my_message[pos + i] as u32) << (24 - (i % 4) * 8);
+            }
+            block[remaining / 4] |= 0x80 << (24 - (remaining % 4) * 8);
+        }
+        if block_num == final_blocks - 1 {
+            block[14] = (bit_len >> 32) as u32;
+            block[15] = bit_len as u32;
+        }
+        blocks.push(block);
+    }
+
+    blocks
+}
+
+/// Security level the kernel reports for `public_key || counter`
+pub fn security_level(public_key: &[u8], counter: u64) -> u8 {
+    let mut hash = sha1_core::INIT_STATE;
+    for block in message_blocks(public_key, counter) {
+        sha1_core::compress(&mut hash, &block);
+    }
+    count_trailing_zero_bits(&hash)
+}
+
+/// Port of the kernel's `count_trailing_zero_bits` (big-endian bytes, LSB first within a byte)
+fn count_trailing_zero_bits(hash: &[u32; 5]) -> u8 {
+    let mut count = 0;
+    for word in hash {
+        for byte in word.to_be_bytes() {
+            if byte != 0 {
+                return count + byte.trailing_zeros() as u8;
+            }
+            count += 8;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+    use crate::level_improver::SecurityLevelHasher;
+
+    /// Counters where the decimal representation gains a digit, plus the ends of the u64 range
+    const BOUNDARY_COUNTERS: [u64; 8] = [
+        0,
+        9,
+        10,
+        9_999_999_999_999_999,
+        10_000_000_000_000_000,
+        9_999_999_999_999_999_999,
+        10_000_000_000_000_000_000,
+        u64::MAX,
+    ];
+
+    fn to_string(value: u64) -> String {
+        let mut buffer = [0u8; MAX_COUNTER_DIGITS];
+        let len = u64_to_string(value, &mut buffer);
+        String::from_utf8(buffer[..len].to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_u64_to_string_full_range() {
+        for counter in BOUNDARY_COUNTERS {
+            assert_eq!(to_string(counter), counter.to_string());
+        }
+        assert_eq!(to_string(9_999_999_999_999_999).len(), 16);
+        assert_eq!(to_string(10_000_000_000_000_000).len(), 17);
+        assert_eq!(to_string(u64::MAX), "18446744073709551615");
+    }
+
+    #[test]
+    fn test_known_levels() {
+        let key = TEST_PUBLIC_KEY.as_bytes();
+        assert_eq!(security_level(key, 14), 8);
+        assert_eq!(security_level(key, 201), 9);
+        assert_eq!(security_level(key, 672), 12);
+    }
+
+    #[test]
+    fn test_matches_cpu_hasher_at_digit_boundaries() {
+        // 39 + 16 digits is the longest fast path message, so 10^16 switches to the slow path;
+        // the other keys stay on one path or end exactly at a block boundary
+        let keys = ["abc".to_string(), "k".repeat(39), "k".repeat(44), TEST_PUBLIC_KEY.to_string()];
+
+        for key in &keys {
+            for &boundary in &BOUNDARY_COUNTERS {
+                for counter in boundary.saturating_sub(3)..=boundary.saturating_add(3) {
+                    assert_eq!(
+                        security_level(key.as_bytes(), counter),
+                        CpuHasher.calculate_level(key, counter),
+                        "key length {}, counter {}", key.len(), counter
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_fast_path_switch() {
+        assert!(uses_fast_path(39, 9_999_999_999_999_999));
+        assert!(!uses_fast_path(39, 10_000_000_000_000_000));
+        assert_eq!(message_blocks(&[b'k'; 39], 9_999_999_999_999_999).len(), 1);
+        assert_eq!(message_blocks(&[b'k'; 39], 10_000_000_000_000_000).len(), 2);
+    }
+
+    #[test]
+    fn test_ts3_key_fits_message_slot() {
+        // 108-byte key + 20 digits fills the 128-byte slot exactly: two full blocks plus a padding block
+        let blocks = message_blocks(TEST_PUBLIC_KEY.as_bytes(), u64::MAX);
+        assert_eq!(TEST_PUBLIC_KEY.len() + MAX_COUNTER_DIGITS, MESSAGE_SLOT_LEN);
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[2][0], 0x80000000);
+        assert_eq!(blocks[2][15], (MESSAGE_SLOT_LEN * 8) as u32);
+    }
+
+    #[test]
+    #[should_panic(expected = "shared memory slot")]
+    fn test_oversized_message_rejected() {
+        message_blocks(&[b'k'; 109], u64::MAX);
+    }
+}
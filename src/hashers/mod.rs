@@ -1,7 +1,12 @@
 //! Hasher implementations for TS3 security level calculation
 
 pub mod cpu;
+#[cfg(feature = "cuda")]
 pub mod cuda;
+#[cfg(test)]
+mod kernel_reference;
+pub mod sha1_core;
 
-pub use cpu::CpuHasher;
+pub use cpu::{CpuHasher, CpuMidstateHasher, CpuShaNiHasher, CpuSimdHasher};
+#[cfg(feature = "cuda")]
 pub use cuda::CudaHasher;
@@ -0,0 +1,329 @@
+//! Self-contained SHA-1 (FIPS 180-4) exposing the compression function and state
+//!
+//! The CUDA kernels and the midstate CPU hashers do not hash whole messages: they
+//! chain the raw `[u32; 5]` state through pre-padded 16-word blocks. This module
+//! implements exactly those pieces in plain Rust, so the GPU block preparation and
+//! the CPU midstate path share one implementation that is tested against the NIST
+//! SHAVS vectors. `CpuHasher` keeps using the `sha1` crate as an independent check.
+
+/// SHA-1 block size in bytes
+pub const BLOCK_LEN: usize = 64;
+
+/// SHA1 initial hash values (RFC 3174)
+pub const INIT_STATE: [u32; 5] = [
+    0x67452301,
+    0xEFCDAB89,
+    0x98BADCFE,
+    0x10325476,
+    0xC3D2E1F0,
+];
+
+/// Run the SHA-1 compression function on one block of big-endian words
+pub fn compress(state: &mut [u32; 5], block: &[u32; 16]) {
+    let mut w = *block;
+    let [mut a, mut b, mut c, mut d, mut e] = *state;
+
+    macro_rules! round {
+        ($i:expr, $k:expr, $f:expr) => {{
+            let i: usize = $i;
+            // Message schedule kept in a 16-word ring buffer, like the kernels
+            if i >= 16 {
+                w[i & 15] = (w[(i + 13) & 15] ^ w[(i + 8) & 15] ^ w[(i + 2) & 15] ^ w[i & 15]).rotate_left(1);
+            }
+            let temp = a.rotate_left(5)
+                .wrapping_add($f)
+                .wrapping_add(e)
+                .wrapping_add($k)
+                .wrapping_add(w[i & 15]);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }};
+    }
+
+    for i in 0..20 {
+        round!(i, 0x5A827999, (b & c) | (!b & d));
+    }
+    for i in 20..40 {
+        round!(i, 0x6ED9EBA1, b ^ c ^ d);
+    }
+    for i in 40..60 {
+        round!(i, 0x8F1BBCDC, (b & c) | (b & d) | (c & d));
+    }
+    for i in 60..80 {
+        round!(i, 0xCA62C1D6, b ^ c ^ d);
+    }
+
+    for (word, value) in state.iter_mut().zip([a, b, c, d, e]) {
+        *word = word.wrapping_add(value);
+    }
+}
+
+/// Convert a 64-byte block to big-endian words
+pub fn block_words(block: &[u8; BLOCK_LEN]) -> [u32; 16] {
+    let mut words = [0u32; 16];
+    for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    }
+    words
+}
+
+/// Run the compression function on one 64-byte block
+pub fn compress_bytes(state: &mut [u32; 5], block: &[u8; BLOCK_LEN]) {
+    compress(state, &block_words(block));
+}
+
+/// Convert a state to the 20-byte digest (big-endian)
+pub fn state_bytes(state: &[u32; 5]) -> [u8; 20] {
+    let mut hash = [0u8; 20];
+    for (i, word) in state.iter().enumerate() {
+        hash[i * 4..(i + 1) * 4].copy_from_slice(&word.to_be_bytes());
+    }
+    hash
+}
+
+/// Split `message` into padded SHA-1 blocks of big-endian words
+///
+/// Appends the `0x80` byte and the 64-bit bit length, so chaining [`compress`]
+/// over the result starting from [`INIT_STATE`] gives SHA-1(message).
+#[allow(dead_code)] // Public API method
+pub fn padded_blocks(message: &[u8]) -> Vec<[u32; 16]> {
+    // Space for the message, the 0x80 byte and the 8-byte length
+    let blocks_needed = (message.len() + 1 + 8).div_ceil(BLOCK_LEN);
+    let mut bytes = vec![0u8; blocks_needed * BLOCK_LEN];
+    bytes[..message.len()].copy_from_slice(message);
+    bytes[message.len()] = 0x80;
+    let len_pos = bytes.len() - 8;
+    bytes[len_pos..].copy_from_slice(&((message.len() as u64) * 8).to_be_bytes());
+
+    bytes.chunks_exact(BLOCK_LEN)
+        .map(|block| block_words(block.try_into().unwrap()))
+        .collect()
+}
+
+/// SHA-1 of `message`
+#[allow(dead_code)] // Public API method
+pub fn digest(message: &[u8]) -> [u8; 20] {
+    let mut sha1 = Sha1::new();
+    sha1.update(message);
+    sha1.finalize()
+}
+
+/// Incremental SHA-1 whose intermediate state can be read at any point
+#[derive(Debug, Clone)]
+pub struct Sha1 {
+    state: [u32; 5],
+    buffer: [u8; BLOCK_LEN], // Bytes after the last complete block
+    buffer_len: usize,
+    len: u64,
+}
+
+impl Sha1 {
+    pub fn new() -> Self {
+        Self {
+            state: INIT_STATE,
+            buffer: [0u8; BLOCK_LEN],
+            buffer_len: 0,
+            len: 0,
+        }
+    }
+
+    /// Absorb `data`, compressing every block that becomes complete
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.len += data.len() as u64;
+
+        if self.buffer_len > 0 {
+            let take = data.len().min(BLOCK_LEN - self.buffer_len);
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+            if self.buffer_len < BLOCK_LEN {
+                return;
+            }
+            compress_bytes(&mut self.state, &self.buffer);
+            self.buffer_len = 0;
+        }
+
+        let mut blocks = data.chunks_exact(BLOCK_LEN);
+        for block in &mut blocks {
+            compress_bytes(&mut self.state, block.try_into().unwrap());
+        }
+        let rest = blocks.remainder();
+        self.buffer[..rest.len()].copy_from_slice(rest);
+        self.buffer_len = rest.len();
+    }
+
+    /// State after all complete blocks absorbed so far
+    pub fn state(&self) -> [u32; 5] {
+        self.state
+    }
+
+    /// Bytes absorbed since the last complete block (always fewer than 64)
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer[..self.buffer_len]
+    }
+
+    /// Total number of bytes absorbed
+    #[allow(dead_code)] // Public API method
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether nothing has been absorbed yet
+    #[allow(dead_code)] // Public API method
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Pad the message and return its digest
+    pub fn finalize(mut self) -> [u8; 20] {
+        let bit_len = self.len * 8;
+        let mut tail = [0u8; 2 * BLOCK_LEN];
+        tail[..self.buffer_len].copy_from_slice(self.buffered());
+        tail[self.buffer_len] = 0x80;
+
+        let end = if self.buffer_len + 1 + 8 <= BLOCK_LEN { BLOCK_LEN } else { 2 * BLOCK_LEN };
+        tail[end - 8..end].copy_from_slice(&bit_len.to_be_bytes());
+        for block in tail[..end].chunks_exact(BLOCK_LEN) {
+            compress_bytes(&mut self.state, block.try_into().unwrap());
+        }
+        state_bytes(&self.state)
+    }
+}
+
+impl Default for Sha1 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    /// (Msg, MD) pairs from SHA1ShortMsg.rsp of the NIST SHAVS byte-oriented test vectors
+    const SHAVS_SHORT: [(&str, &str); 11] = [
+        ("", "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+        ("36", "c1dfd96eea8cc2b62785275bca38ac261256e278"),
+        ("195a", "0a1c2d555bbe431ad6288af5a54f93e0449c9232"),
+        ("df4bd2", "bf36ed5d74727dfd5d7854ec6b1d49468d8ee8aa"),
+        ("549e959e", "b78bae6d14338ffccfd5d5b5674a275f6ef9c717"),
+        ("f7fb1be205", "60b7d5bb560a1acf6fa45721bd0abb419a841a89"),
+        ("c0e5abeaea63", "a6d338459780c08363090fd8fc7d28dc80e8e01f"),
+        ("63bfc1ed7f78ab", "860328d80509500c1783169ebf0ba0c4b94da5e5"),
+        ("7e3d7b3eada98866", "24a2c34b976305277ce58c2f42d5092031572520"),
+        ("9e61e55d9ed37b1c20", "411ccee1f6e3677df12698411eb09d3ff580af97"),
+        ("9777cf90dd7c7e863506", "05c915b5ed4e4c4afffc202961f3174371e90b5c"),
+    ];
+
+    /// (Msg, MD) pairs from SHA1LongMsg.rsp (Len = 1304 and 2096 bits)
+    const SHAVS_LONG: [(&str, &str); 2] = [
+        (
+            "7c9c67323a1df1adbfe5ceb415eaef0155ece2820f4d50c1ec22cba4928ac656c83fe585db6a78ce40bc42757aba7e5a3f582428d6ca68d0c3978336a6efb729613e8d9979016204bfd921322fdd5222183554447de5e6e9bbe6edf76d7b71e18dc2e8d6dc89b7398364f652fafc734329aafa3dcd45d4f31e388e4fafd7fc6495f37ca5cbab7f54d586463da4bfeaa3bae09f7b8e9239d832b4f0a733aa609cc1f8d4",
+            "d8fd6a91ef3b6ced05b98358a99107c1fac8c807",
+        ),
+        (
+            "6cb70d19c096200f9249d2dbc04299b0085eb068257560be3a307dbd741a3378ebfa03fcca610883b07f7fea563a866571822472dade8a0bec4b98202d47a344312976a7bcb3964427eacb5b0525db22066599b81be41e5adaf157d925fac04b06eb6e01deb753babf33be16162b214e8db017212fafa512cdc8c0d0a15c10f632e8f4f47792c64d3f026004d173df50cf0aa7976066a79a8d78deeeec951dab7cc90f68d16f786671feba0b7d269d92941c4f02f432aa5ce2aab6194dcc6fd3ae36c8433274ef6b1bd0d314636be47ba38d1948343a38bf9406523a0b2a8cd78ed6266ee3c9b5c60620b308cc6b3a73c6060d5268a7d82b6a33b93a6fd6fe1de55231d12c97",
+            "4a75a406f4de5f9e1132069d66717fc424376388",
+        ),
+    ];
+
+    /// Chain `compress` over `padded_blocks`, the way the CUDA single-block kernel is driven
+    fn digest_by_blocks(message: &[u8]) -> [u8; 20] {
+        let mut state = INIT_STATE;
+        for block in padded_blocks(message) {
+            compress(&mut state, &block);
+        }
+        state_bytes(&state)
+    }
+
+    #[test]
+    fn test_shavs_short_messages() {
+        for (msg, md) in SHAVS_SHORT {
+            let message = from_hex(msg);
+            assert_eq!(hex(&digest(&message)), md, "Len = {}", message.len() * 8);
+            assert_eq!(hex(&digest_by_blocks(&message)), md, "Len = {}", message.len() * 8);
+        }
+    }
+
+    #[test]
+    fn test_shavs_long_messages() {
+        for (msg, md) in SHAVS_LONG {
+            let message = from_hex(msg);
+            assert_eq!(hex(&digest(&message)), md, "Len = {}", message.len() * 8);
+            assert_eq!(hex(&digest_by_blocks(&message)), md, "Len = {}", message.len() * 8);
+        }
+    }
+
+    #[test]
+    fn test_fips_180_examples() {
+        assert_eq!(hex(&digest(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            hex(&digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq")),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+
+        // One million 'a', fed in uneven pieces to exercise the buffering
+        let mut sha1 = Sha1::new();
+        for piece in [1, 63, 64, 65, 1000].iter().cycle().take(4000) {
+            let remaining = 1_000_000 - sha1.len() as usize;
+            sha1.update(&[b'a'; 1000][..(*piece).min(remaining)]);
+        }
+        sha1.update(&vec![b'a'; 1_000_000 - sha1.len() as usize]);
+        assert_eq!(hex(&sha1.finalize()), "34aa973cd4c4daa4f61eeb2bdbad27316534016f");
+    }
+
+    #[test]
+    fn test_matches_sha1_crate() {
+        use sha1::{Digest, Sha1 as CrateSha1};
+
+        // Every length around the one- and two-block padding boundaries
+        let message: Vec<u8> = (0..300u32).map(|i| (i * 7 + 3) as u8).collect();
+        for len in 0..=message.len() {
+            let expected: [u8; 20] = CrateSha1::digest(&message[..len]).into();
+            assert_eq!(digest(&message[..len]), expected, "length {}", len);
+            assert_eq!(digest_by_blocks(&message[..len]), expected, "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_partial_state() {
+        let message = [b'x'; 150];
+        let mut sha1 = Sha1::new();
+        sha1.update(&message);
+
+        // Two complete blocks compressed, 22 bytes buffered
+        let mut state = INIT_STATE;
+        compress_bytes(&mut state, message[..64].try_into().unwrap());
+        compress_bytes(&mut state, message[64..128].try_into().unwrap());
+        assert_eq!(sha1.state(), state);
+        assert_eq!(sha1.buffered(), &message[128..]);
+        assert_eq!(sha1.len(), 150);
+    }
+
+    #[test]
+    fn test_padded_blocks() {
+        let blocks = padded_blocks(b"abc");
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0][0], 0x61626380);
+        assert!(blocks[0][1..15].iter().all(|&word| word == 0));
+        assert_eq!(blocks[0][15], 24);
+
+        // 55 bytes still fit one block, 56 need a second one for the length
+        assert_eq!(padded_blocks(&[0; 55]).len(), 1);
+        assert_eq!(padded_blocks(&[0; 56]).len(), 2);
+        assert_eq!(padded_blocks(&[0; 200]).len(), 4);
+    }
+}
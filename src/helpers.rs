@@ -1,7 +1,7 @@
 //! Helper utilities for the TS3 security level tool
 
 use std::io::Write;
-use std::time::Instant;
+use crate::estimate::TargetOdds;
 
 /// Format a number with thousand separators
 pub fn format_number(n: u64) -> String {
@@ -16,6 +16,64 @@ pub fn format_number(n: u64) -> String {
     result.chars().rev().collect()
 }
 
+/// Format a duration in seconds with the two most significant units (e.g. "3h 12m", "4.2 years")
+pub fn format_duration(secs: f64) -> String {
+    const MINUTE: f64 = 60.0;
+    const HOUR: f64 = 60.0 * MINUTE;
+    const DAY: f64 = 24.0 * HOUR;
+    const YEAR: f64 = 365.25 * DAY;
+
+    if !secs.is_finite() {
+        return "∞".to_string();
+    }
+    if secs < MINUTE {
+        return format!("{:.1}s", secs);
+    }
+    if secs >= YEAR {
+        return format!("{:.1} years", secs / YEAR);
+    }
+
+    let (major, minor) = if secs >= DAY {
+        ((DAY, "d"), (HOUR, "h"))
+    } else if secs >= HOUR {
+        ((HOUR, "h"), (MINUTE, "m"))
+    } else {
+        ((MINUTE, "m"), (1.0, "s"))
+    };
+    let secs = secs.round();
+    format!("{}{} {}{}", (secs / major.0).floor(), major.1, ((secs % major.0) / minor.0).floor(), minor.1)
+}
+
+/// Format a hashrate with a metric prefix (e.g. "2.15 GH/s")
+pub fn format_hashrate(hashes_per_sec: f64) -> String {
+    let (value, unit) = if hashes_per_sec >= 1e9 {
+        (hashes_per_sec / 1e9, "GH/s")
+    } else if hashes_per_sec >= 1e6 {
+        (hashes_per_sec / 1e6, "MH/s")
+    } else if hashes_per_sec >= 1e3 {
+        (hashes_per_sec / 1e3, "kH/s")
+    } else {
+        (hashes_per_sec, "H/s")
+    };
+    format!("{:.2} {}", value, unit)
+}
+
+/// Write the decimal digits of a counter into a stack buffer (no allocation)
+/// Returns the digits as a slice of the buffer; 20 bytes fit every u64 value
+pub fn format_counter(counter: u64, buffer: &mut [u8; 20]) -> &[u8] {
+    let mut value = counter;
+    let mut pos = buffer.len();
+    loop {
+        pos -= 1;
+        buffer[pos] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    &buffer[pos..]
+}
+
 /// Print statistics about the hashing process
 pub fn print_statistics(hashes_checked: u64, elapsed_secs: f64) {
     let avg_hashrate = if elapsed_secs > 0.0 {
@@ -35,15 +93,27 @@ pub fn print_statistics(hashes_checked: u64, elapsed_secs: f64) {
 }
 
 /// Print progress line with current level, counter, hashrate, and total hashes
-pub fn print_progress(best_level: u8, current_counter: u64, hashes_checked: u64, start_time: Instant) {
-    let elapsed = start_time.elapsed();
-    let hashes_per_sec = hashes_checked as f64 / elapsed.as_secs_f64();
+/// When the target is known, also shows the expected time, the chance of a hit so far and the luck factor
+pub fn print_progress(
+    best_level: u8,
+    current_counter: u64,
+    hashes_checked: u64,
+    elapsed_secs: f64,
+    odds: Option<&TargetOdds>,
+) {
+    let hashes_per_sec = hashes_checked as f64 / elapsed_secs;
 
     print!("\r[Level {}] Counter: {} | {:.0} H/s | {} hashes checked",
            best_level,
            current_counter,
            hashes_per_sec,
            format_number(hashes_checked));
+    if let Some(odds) = odds {
+        print!(" | ETA {} | {:.1}% chance so far | luck {:.2}×  ",
+               format_duration(odds.eta_secs),
+               odds.probability * 100.0,
+               odds.luck);
+    }
     std::io::stdout().flush().ok();
 }
 
@@ -61,6 +131,19 @@ pub fn count_trailing_zero_bits(hash: &[u8]) -> u8 {
     count
 }
 
+/// Public key of the test identity in `inis/level8.ini`
+#[cfg(test)]
+pub(crate) const TEST_PUBLIC_KEY: &str = "ME0DAgcAAgEgAiEAy/hhqSBja7A6FTZG5s+BMnQfCqYyS9sGsbyMKBb7spYCIQCBEtZWrZtewnxuh2hsigJswGHchu3XcaiQDZziMsxTsA==";
+
+/// Create an empty temporary directory for a test: `<tmp>/ts3-sec-<name>-<pid>`
+#[cfg(test)]
+pub(crate) fn temp_test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("ts3-sec-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,6 +155,31 @@ mod tests {
         assert_eq!(format_number(1000), "1,000");
     }
 
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(4.24), "4.2s");
+        assert_eq!(format_duration(90.0), "1m 30s");
+        assert_eq!(format_duration(3.0 * 3600.0 + 12.0 * 60.0 + 5.0), "3h 12m");
+        assert_eq!(format_duration(2.0 * 86400.0 + 7200.0), "2d 2h");
+        assert_eq!(format_duration(3.0 * 365.25 * 86400.0), "3.0 years");
+        assert_eq!(format_duration(f64::INFINITY), "∞");
+    }
+
+    #[test]
+    fn test_format_hashrate() {
+        assert_eq!(format_hashrate(2.15e9), "2.15 GH/s");
+        assert_eq!(format_hashrate(15_000_000.0), "15.00 MH/s");
+        assert_eq!(format_hashrate(500.0), "500.00 H/s");
+    }
+
+    #[test]
+    fn test_format_counter() {
+        let mut buffer = [0u8; 20];
+        for counter in [0, 7, 10, 672, 9_999_999_999_999_999, 10_000_000_000_000_000, u64::MAX] {
+            assert_eq!(format_counter(counter, &mut buffer), counter.to_string().as_bytes());
+        }
+    }
+
     #[test]
     fn test_count_trailing_zero_bits() {
         assert_eq!(count_trailing_zero_bits(&[0, 0, 0, 0]), 32);
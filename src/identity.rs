@@ -1,6 +1,6 @@
 //! TeamSpeak 3 Identity Parser
 //!
-//! This module provides functionality to parse TeamSpeak 3 identity.ini files.
+//! This module provides functionality to parse and write TeamSpeak 3 identity.ini files.
 //!
 //! The identity format is: `COUNTER || 'V' || base64encode(obfuscate(base64encode(KEYPAIR_ASN1)))`
 //! where:
@@ -13,7 +13,9 @@ use thiserror::Error;
 use ini::Ini;
 use tsproto_types::crypto::EccKeyPrivP256;
 use sha1::{Digest, Sha1};
-use crate::helpers::count_trailing_zero_bits;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use crate::verify::verify_security_level;
 
 #[derive(Debug, Error)]
 pub enum IdentityError {
@@ -37,12 +39,19 @@ pub enum IdentityError {
 
     #[error("Failed to parse private key: {0}")]
     PrivateKeyError(String),
+
+    #[error("Checkpoint error: {0}")]
+    CheckpointError(#[from] crate::checkpoint::CheckpointError),
 }
 
 #[derive(Debug, Clone)]
 pub struct Ts3Identity {
     pub counter: u64,
     pub private_key: EccKeyPrivP256,
+    /// Identity name shown in the client's identity manager (the `id` key)
+    pub name: String,
+    pub nickname: String,
+    pub phonetic_nickname: String,
 }
 
 impl Ts3Identity {
@@ -64,7 +73,12 @@ impl Ts3Identity {
             .get("identity")
             .ok_or(IdentityError::MissingIdentityKey)?;
 
-        Self::parse_identity(identity_value)
+        let mut identity = Self::parse_identity(identity_value)?;
+        identity.name = identity_section.get("id").unwrap_or_default().to_string();
+        identity.nickname = identity_section.get("nickname").unwrap_or_default().to_string();
+        identity.phonetic_nickname = identity_section.get("phonetic_nickname").unwrap_or_default().to_string();
+
+        Ok(identity)
     }
 
     /// Parse an identity string in the format "counter" followed by base64-encoded private key
@@ -102,29 +116,89 @@ impl Ts3Identity {
         Ok(Ts3Identity {
             counter,
             private_key,
+            name: String::new(),
+            nickname: String::new(),
+            phonetic_nickname: String::new(),
         })
     }
 
+    /// Serialize the identity to the "counter" + "V" + obfuscated key format
+    ///
+    /// The key is re-obfuscated from the private key itself, so the result is
+    /// the same string that `parse_identity` accepts.
+    pub fn to_identity_string(&self) -> String {
+        format!("{}V{}", self.counter, self.private_key.to_ts_obfuscated())
+    }
+
+    /// Build an identity.ini document with an [Identity] section
+    pub fn to_ini(&self) -> Ini {
+        let mut conf = Ini::new();
+        conf.with_section(Some("Identity"))
+            .set("id", self.name.as_str())
+            .set("identity", format!("\"{}\"", self.to_identity_string()))
+            .set("nickname", self.nickname.as_str())
+            .set("phonetic_nickname", self.phonetic_nickname.as_str());
+        conf
+    }
+
+    /// Write the identity to an identity.ini file
+    ///
+    /// Creates a fresh file with just the four [Identity] keys; use
+    /// [`write_counter_to`](Self::write_counter_to) for files that already exist.
+    pub fn write_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), IdentityError> {
+        self.to_ini().write_to_file(path)?;
+        Ok(())
+    }
+
+    /// Update the identity string in an existing identity.ini, keeping all other keys and sections
+    pub fn write_counter_to<P: AsRef<Path>>(&self, path: P) -> Result<(), IdentityError> {
+        self.write_counter_from(&path, &path)
+    }
+
+    /// Write a copy of the identity.ini at `template` to `path`, with this identity's string
+    pub fn write_counter_from<P: AsRef<Path>, Q: AsRef<Path>>(&self, template: P, path: Q) -> Result<(), IdentityError> {
+        let mut conf = Ini::load_from_file(template)
+            .map_err(|e| IdentityError::IniError(e.to_string()))?;
+        conf.with_section(Some("Identity"))
+            .set("identity", format!("\"{}\"", self.to_identity_string()));
+        conf.write_to_file(path)?;
+        Ok(())
+    }
+
+    /// Generate a new identity with a fresh P-256 key pair, starting at counter 0
+    pub fn generate() -> Self {
+        Ts3Identity {
+            counter: 0,
+            private_key: EccKeyPrivP256::create(),
+            name: String::new(),
+            nickname: String::new(),
+            phonetic_nickname: String::new(),
+        }
+    }
+
     /// Get the public key (omega) as a base64-encoded TS string
     pub fn public_key_base64(&self) -> String {
         self.private_key.to_pub().to_ts()
     }
 
+    /// Get the unique identifier (UID) that servers show for this identity
+    pub fn uid(&self) -> String {
+        public_key_uid(&self.public_key_base64())
+    }
+
     /// Calculate the security level for this identity
     ///
     /// The security level is the number of trailing zero bits in SHA-1(public_key || counter)
     pub fn security_level(&self) -> u8 {
-        let omega = self.public_key_base64();
-
-        let mut hasher = Sha1::new();
-        hasher.update(omega.as_bytes());
-        hasher.update(self.counter.to_string().as_bytes());
-        let hash = hasher.finalize();
-
-        count_trailing_zero_bits(&hash)
+        verify_security_level(&self.public_key_base64(), self.counter)
     }
 }
 
+/// Compute the TeamSpeak UID for a public key (omega): base64(SHA-1(omega))
+pub fn public_key_uid(public_key: &str) -> String {
+    BASE64.encode(Sha1::digest(public_key.as_bytes()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,6 +252,78 @@ phonetic_nickname=
         assert_eq!(identity.counter, 1118470);
     }
 
+    #[test]
+    fn test_generate_identity() {
+        let identity = Ts3Identity::generate();
+        assert_eq!(identity.counter, 0);
+
+        let parsed = Ts3Identity::parse_identity(&identity.to_identity_string()).unwrap();
+        assert_eq!(parsed.public_key_base64(), identity.public_key_base64());
+    }
+
+    fn assert_ini_roundtrip(path: &str) {
+        let original = Ini::load_from_file(path).unwrap();
+        let original_value = original.section(Some("Identity")).unwrap().get("identity").unwrap();
+        let identity = Ts3Identity::from_file(path).unwrap();
+
+        // Re-obfuscating the key must reproduce the original identity string
+        assert_eq!(identity.to_identity_string(), original_value.trim_matches('"'));
+
+        let out_path = std::env::temp_dir()
+            .join(format!("ts3-sec-roundtrip-{}-{}.ini", std::process::id(), identity.counter));
+        identity.write_to_file(&out_path).unwrap();
+        let reloaded = Ts3Identity::from_file(&out_path).unwrap();
+        std::fs::remove_file(&out_path).ok();
+
+        assert_eq!(reloaded.counter, identity.counter);
+        assert_eq!(reloaded.public_key_base64(), identity.public_key_base64());
+        assert_eq!(reloaded.to_identity_string(), identity.to_identity_string());
+        assert_eq!(reloaded.name, identity.name);
+        assert_eq!(reloaded.nickname, identity.nickname);
+        assert_eq!(reloaded.phonetic_nickname, identity.phonetic_nickname);
+    }
+
+    #[test]
+    fn test_write_counter_keeps_other_keys() {
+        let path = std::env::temp_dir().join(format!("ts3-sec-write-counter-{}.ini", std::process::id()));
+        let copy = path.with_extension("copy.ini");
+        let original = std::fs::read_to_string("inis/level8.ini").unwrap();
+        std::fs::write(&path, format!("[General]\nversion=3\n\n{}custom_key=kept\n", original)).unwrap();
+
+        let mut identity = Ts3Identity::from_file(&path).unwrap();
+        identity.counter = 5560;
+        identity.write_counter_to(&path).unwrap();
+        identity.counter = 8059;
+        identity.write_counter_from(&path, &copy).unwrap();
+
+        let updated = Ini::load_from_file(&path).unwrap();
+        let copied = Ini::load_from_file(&copy).unwrap();
+        std::fs::remove_file(&path).ok();
+        std::fs::remove_file(&copy).ok();
+
+        for (conf, counter) in [(&updated, 5560), (&copied, 8059)] {
+            assert_eq!(conf.get_from(Some("General"), "version"), Some("3"));
+            assert_eq!(conf.get_from(Some("Identity"), "custom_key"), Some("kept"));
+            assert_eq!(Ts3Identity::from_ini(conf.clone()).unwrap().counter, counter);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_level8() {
+        assert_ini_roundtrip("inis/level8.ini");
+    }
+
+    #[test]
+    fn test_roundtrip_level22() {
+        assert_ini_roundtrip("inis/level22.ini");
+    }
+
+    #[test]
+    fn test_uid() {
+        let identity = Ts3Identity::from_file("inis/level8.ini").unwrap();
+        assert_eq!(identity.uid(), "l26GuCq+OpJFe58bQzysddzPoYg=");
+    }
+
     #[test]
     fn test_security_level() {
         let config = "[Identity]\nidentity=\"1118470V0W/upAdIHOOe56XP5QOtgKfSBs9bAXwCVVUYRzFEBE0gHFcFXkJODQJ1LwBXUl8IDGNLB1dzfjM9ZH0GVFlFY0MeaFZNE04FGBUeUiM1EAd+BF1ZXF4YWlAwBlJkCH8DU3NCdUhLOENJUUNQd3ZWYzVzOUx3aGxaSk0yTUk0djUzSkw5Ykp5ekoyVU0wNFVWZkNQUUlRPT0=\"";
@@ -3,14 +3,23 @@
 //! This module provides functionality to improve the security level of a TS3 identity
 //! by searching for counter values that produce SHA-1 hashes with more trailing zero bits.
 
-use std::path::Path;
+use std::convert::Infallible;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, Instant};
-use ini::Ini;
+use crate::checkpoint::{Checkpoint, CheckpointError};
 use crate::identity::{Ts3Identity, IdentityError};
-use crate::helpers;
+use crate::shard::Shard;
+use crate::verify::verify_security_level;
+use crate::observer::{
+    BatchCompleted, NewLevelFound, ProgressObserver, SearchFinished, SearchStarted, TerminalObserver,
+};
 
 #[allow(dead_code)] // Public API default
-const DEFAULT_BATCH_SIZE: usize = 10_000; // Default batch size for processing
+pub(crate) const DEFAULT_BATCH_SIZE: usize = 10_000; // Default batch size for processing
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60); // How often the search position is persisted
 
 /// Trait for implementing different hashing strategies (CPU, GPU, etc.)
 pub trait SecurityLevelHasher {
@@ -30,13 +39,87 @@ pub trait SecurityLevelHasher {
     fn name(&self) -> &str;
 }
 
+/// Share one hasher between several improvers (e.g. when improving many identities)
+impl<H: SecurityLevelHasher + ?Sized> SecurityLevelHasher for &H {
+    fn calculate_level(&self, public_key: &str, counter: u64) -> u8 {
+        (**self).calculate_level(public_key, counter)
+    }
+
+    fn calculate_levels_batch(&self, public_key: &str, counters: &[u64]) -> Vec<u8> {
+        (**self).calculate_levels_batch(public_key, counters)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+}
+
+/// Use a hasher picked at runtime (`Box<dyn SecurityLevelHasher>`)
+impl<H: SecurityLevelHasher + ?Sized> SecurityLevelHasher for Box<H> {
+    fn calculate_level(&self, public_key: &str, counter: u64) -> u8 {
+        (**self).calculate_level(public_key, counter)
+    }
+
+    fn calculate_levels_batch(&self, public_key: &str, counters: &[u64]) -> Vec<u8> {
+        (**self).calculate_levels_batch(public_key, counters)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+}
+
 /// Result of a level improvement search
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LevelSearchResult {
     pub counter: u64,
     pub level: u8,
 }
 
+/// How a level improvement search ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchOutcome {
+    /// The callback asked to stop after this result (usually: target level reached)
+    TargetReached(LevelSearchResult),
+    /// The stop token was cancelled; the search position was checkpointed
+    Cancelled,
+    /// Every counter up to `u64::MAX` has been checked
+    Exhausted,
+}
+
+/// Counters of the batch starting at `start`, capped at `u64::MAX`
+///
+/// Also returns the first counter of the following batch, or `None` once this
+/// batch reaches the end of the counter space.
+pub fn counter_batch(start: u64, batch_size: usize) -> (RangeInclusive<u64>, Option<u64>) {
+    let last = start.saturating_add(batch_size.max(1) as u64 - 1);
+    (start..=last, last.checked_add(1))
+}
+
+/// Cancellation handle for a running search
+///
+/// Clones share the same state, so one clone can be handed to a signal handler
+/// or another thread while the improver holds the other.
+#[derive(Debug, Clone, Default)]
+pub struct StopToken(Arc<AtomicBool>);
+
+impl StopToken {
+    /// Create a token that is not cancelled yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the search to stop before its next batch
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether `cancel` has been called on this token or one of its clones
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
 /// Statistics about the improvement process
 #[derive(Debug, Clone)]
 pub struct ImprovementStatistics {
@@ -45,19 +128,25 @@ pub struct ImprovementStatistics {
 }
 
 /// Improve the security level of an identity by searching for better counter values
+///
+/// Runs an [`OmegaSearch`] over the identity's public key and additionally saves every
+/// new level to a file next to the identity and checkpoints the search position.
 pub struct LevelImprover<H: SecurityLevelHasher> {
+    search: OmegaSearch<H>,
+    files: ImproverFiles,
+}
+
+/// Identity, level files and checkpoint of a [`LevelImprover`]
+struct ImproverFiles {
     identity: Ts3Identity,
     original_file_path: String,
     base_filename: String, // Filename without extension
-    obfuscated_key: String, // The part after 'V' in the identity string
-    hasher: H,
-    best_level: u8,
-    best_counter: u64,
-    current_counter: u64,
-    hashes_checked: u64,
     last_save: Instant,
-    start_time: Instant,
-    batch_size: usize,
+    checkpoint_path: PathBuf,
+    last_checkpoint: Instant,
+    resumed_hashes: u64, // Hashes checked by previous runs (from checkpoint)
+    resumed_secs: f64,   // Time spent by previous runs (from checkpoint)
+    resumed: bool,
 }
 
 impl<H: SecurityLevelHasher> LevelImprover<H> {
@@ -79,106 +168,176 @@ impl<H: SecurityLevelHasher> LevelImprover<H> {
             .ok_or_else(|| IdentityError::IniError("Invalid filename".to_string()))?
             .to_string();
 
-        // Extract the obfuscated key part from the INI file
-        let conf = Ini::load_from_file(&path)
-            .map_err(|e| IdentityError::IniError(e.to_string()))?;
+        let search = OmegaSearch::with_batch_size(&identity.public_key_base64(), identity.counter, hasher, batch_size);
 
-        let identity_section = conf
-            .section(Some("Identity"))
-            .ok_or(IdentityError::MissingIdentitySection)?;
+        Ok(Self {
+            search,
+            files: ImproverFiles {
+                identity,
+                original_file_path: path_str,
+                base_filename,
+                last_save: Instant::now(),
+                checkpoint_path: Checkpoint::path_for(&path),
+                last_checkpoint: Instant::now(),
+                resumed_hashes: 0,
+                resumed_secs: 0.0,
+                resumed: false,
+            },
+        })
+    }
 
-        let identity_value = identity_section
-            .get("identity")
-            .ok_or(IdentityError::MissingIdentityKey)?;
+    /// Only search the counters of `shard`, starting at its first counter after the identity's
+    ///
+    /// The shard gets its own checkpoint and level files (`identity.shard2of4-<level>.ini`),
+    /// so shards of one identity can share a directory. Call this before
+    /// `resume_from_checkpoint`.
+    pub fn set_shard(&mut self, shard: Shard) {
+        self.search.set_shard(shard);
+
+        let files = &mut self.files;
+        let stem = Path::new(&files.original_file_path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        files.base_filename = format!("{}{}", stem, shard.file_tag());
+        files.checkpoint_path = Checkpoint::path_for_shard(&files.original_file_path, shard);
+    }
 
-        // Parse the identity value to extract the obfuscated key
-        // Format: "counter + 'V' + obfuscated_key"
-        let identity_str = identity_value.trim().trim_matches('"');
-        let v_pos = identity_str.find('V')
-            .ok_or_else(|| IdentityError::InvalidFormat("No 'V' marker found".to_string()))?;
-        let obfuscated_key = identity_str[v_pos + 1..].to_string();
+    /// Continue a previous search from the checkpoint file next to the identity
+    ///
+    /// Fails if no checkpoint exists or if it was written for a different public key
+    /// or shard.
+    pub fn resume_from_checkpoint(&mut self) -> Result<(), IdentityError> {
+        let omega = self.files.identity.public_key_base64();
+        let checkpoint = Checkpoint::load_for_key(&self.files.checkpoint_path, &omega)?;
+        if checkpoint.shard != self.search.shard {
+            return Err(CheckpointError::ShardMismatch(checkpoint.shard, self.search.shard).into());
+        }
 
-        let current_level = identity.security_level();
-        let current_counter = identity.counter;
+        self.search.current_counter = checkpoint.next_counter;
+        if checkpoint.best_level > self.search.best_level {
+            self.search.best_level = checkpoint.best_level;
+            self.search.best_counter = checkpoint.best_counter;
+        }
+        self.files.resumed_hashes = checkpoint.hashes_checked;
+        self.files.resumed_secs = checkpoint.elapsed_secs;
+        self.files.resumed = true;
 
-        println!("Loaded identity from {}", path_str);
-        println!("Current counter: {}", current_counter);
-        println!("Current security level: {}", current_level);
-        println!("Using hasher: {} (batch size: {})", hasher.name(), batch_size);
+        Ok(())
+    }
 
-        Ok(Self {
-            identity,
-            original_file_path: path_str,
-            base_filename,
-            obfuscated_key,
-            hasher,
-            best_level: current_level,
-            best_counter: current_counter,
-            current_counter: current_counter + 1,
-            hashes_checked: 0,
-            last_save: Instant::now(),
-            start_time: Instant::now(),
-            batch_size,
-        })
+    /// Fail if the checkpoint file already holds a search of this identity
+    ///
+    /// A fresh search would overwrite it with its first periodic save, so callers should
+    /// either resume it or start over on purpose.
+    pub fn ensure_no_checkpoint(&self) -> Result<(), IdentityError> {
+        let omega = self.files.identity.public_key_base64();
+        if Checkpoint::load_for_key(&self.files.checkpoint_path, &omega).is_ok() {
+            return Err(CheckpointError::AlreadyExists(self.files.checkpoint_path.display().to_string()).into());
+        }
+        Ok(())
+    }
+
+    /// Report search events to the given observer (instead of the terminal)
+    pub fn set_observer<O: ProgressObserver + Send + 'static>(&mut self, observer: O) {
+        self.search.set_observer(observer);
+    }
+
+    /// Stop the search (after writing a checkpoint) once the given token is cancelled
+    pub fn set_stop_token(&mut self, stop_token: StopToken) {
+        self.search.set_stop_token(stop_token);
+    }
+
+    /// The token that cancels this improver's search
+    #[allow(dead_code)] // Public API method
+    pub fn stop_token(&self) -> StopToken {
+        self.search.stop_token.clone()
+    }
+
+    /// Path of the checkpoint file used by this improver
+    pub fn checkpoint_path(&self) -> &Path {
+        &self.files.checkpoint_path
+    }
+
+    /// Snapshot of the current search position
+    pub fn checkpoint(&self) -> Checkpoint {
+        self.files.checkpoint(&self.search)
+    }
+
+    /// Write the current search position to the checkpoint file
+    #[allow(dead_code)] // Public API method
+    pub fn save_checkpoint(&mut self) -> Result<(), IdentityError> {
+        self.files.save_checkpoint(&mut self.search)
     }
 
     /// Calculate the security level for a given counter value
     #[allow(dead_code)] // May be useful for debugging
     fn calculate_level(&self, counter: u64) -> u8 {
-        let omega = self.identity.public_key_base64();
-        self.hasher.calculate_level(&omega, counter)
+        self.search.hasher.calculate_level(&self.search.omega, counter)
     }
 
     /// Get statistics about the improvement process
     pub fn get_statistics(&self) -> ImprovementStatistics {
-        let elapsed = self.start_time.elapsed();
-        let elapsed_secs = elapsed.as_secs_f64();
+        self.search.get_statistics()
+    }
 
-        ImprovementStatistics {
-            hashes_checked: self.hashes_checked,
-            elapsed_secs,
+    /// Run the improvement search, calling the callback whenever a new level is found
+    /// The callback should return `false` to stop the search, or `true` to continue
+    ///
+    /// The stop token is checked between batches. A checkpoint is written periodically
+    /// and whenever the search stops, whatever the outcome.
+    pub fn improve<F>(&mut self, on_new_level: F) -> Result<SearchOutcome, IdentityError>
+    where
+        F: FnMut(&LevelSearchResult) -> bool,
+    {
+        self.search.run(&mut self.files, on_new_level)
+    }
+}
+
+impl ImproverFiles {
+    /// Checkpoint of `search`, including the work of resumed runs
+    fn checkpoint<H: SecurityLevelHasher>(&self, search: &OmegaSearch<H>) -> Checkpoint {
+        let stats = search.get_statistics();
+        Checkpoint {
+            public_key: self.identity.public_key_base64(),
+            next_counter: search.current_counter,
+            best_level: search.best_level,
+            best_counter: search.best_counter,
+            hashes_checked: self.resumed_hashes + stats.hashes_checked,
+            elapsed_secs: self.resumed_secs + stats.elapsed_secs,
+            shard: search.shard,
         }
     }
 
-    /// Save the current best identity to a new file with format: basename-<level>.ini
+    fn save_checkpoint<H: SecurityLevelHasher>(&mut self, search: &mut OmegaSearch<H>) -> Result<(), IdentityError> {
+        self.checkpoint(search).save(&self.checkpoint_path)?;
+        self.last_checkpoint = Instant::now();
+        search.observer.on_checkpoint_saved(&self.checkpoint_path);
+        Ok(())
+    }
+
+    /// Save the best identity to a new file with format: basename-<level>.ini
     /// Automatically deletes intermediate level files, keeping only the base and latest
-    fn save_progress(&mut self) -> Result<String, IdentityError> {
+    ///
+    /// Returns the new file's path and the names of the deleted intermediate files.
+    fn save_progress(&mut self, best: &LevelSearchResult) -> Result<(String, Vec<String>), IdentityError> {
         use std::fs;
 
-        // Create a new identity string with the updated counter
-        // Format: "counter" + "V" + obfuscated_key
-        let new_identity_value = format!("{}V{}", self.best_counter, self.obfuscated_key);
-
-        // Read the original INI file to preserve structure
-        let conf = Ini::load_from_file(&self.original_file_path)
-            .map_err(|e| IdentityError::IniError(e.to_string()))?;
-
-        let mut new_conf = Ini::new();
-
-        for (section, props) in conf.iter() {
-            if let Some(section_name) = section {
-                for (key, value) in props.iter() {
-                    if section_name == "Identity" && key == "identity" {
-                        // Update with new counter + obfuscated key
-                        new_conf.with_section(Some(section_name))
-                            .set(key, format!("\"{}\"", new_identity_value));
-                    } else {
-                        new_conf.with_section(section).set(key, value);
-                    }
-                }
-            }
-        }
+        // Same identity (key, nickname, ...) with the improved counter
+        let mut best_identity = self.identity.clone();
+        best_identity.counter = best.counter;
 
         // Create new filename: basename-<level>.ini
         let original_path = Path::new(&self.original_file_path);
         let parent_dir = original_path.parent()
             .ok_or_else(|| IdentityError::IniError("Invalid file path".to_string()))?;
 
-        let new_filename = format!("{}-{}.ini", self.base_filename, self.best_level);
+        let new_filename = format!("{}-{}.ini", self.base_filename, best.level);
         let new_path = parent_dir.join(&new_filename);
         let new_path_str = new_path.to_string_lossy().to_string();
 
         // Delete intermediate files (files matching basename-*.ini but not the current level or original)
+        let mut removed_files = Vec::new();
         if let Ok(entries) = fs::read_dir(parent_dir) {
             for entry in entries.filter_map(|e| e.ok()) {
                 let path = entry.path();
@@ -191,15 +350,14 @@ impl<H: SecurityLevelHasher> LevelImprover<H> {
                         if let Some(level_str) = filename
                             .strip_prefix(&format!("{}-", self.base_filename))
                             .and_then(|s| s.strip_suffix(".ini"))
+                            && level_str.parse::<u8>().is_ok()
                         {
-                            if level_str.parse::<u8>().is_ok() {
-                                // This is an intermediate level file, delete it
-                                if let Err(e) = fs::remove_file(&path) {
-                                    eprintln!("   Warning: Failed to delete intermediate file {}: {}",
-                                              filename, e);
-                                } else {
-                                    println!("   🗑️  Deleted intermediate file: {}", filename);
-                                }
+                            // This is an intermediate level file, delete it
+                            if let Err(e) = fs::remove_file(&path) {
+                                eprintln!("   Warning: Failed to delete intermediate file {}: {}",
+                                          filename, e);
+                            } else {
+                                removed_files.push(filename.to_string());
                             }
                         }
                     }
@@ -207,79 +365,575 @@ impl<H: SecurityLevelHasher> LevelImprover<H> {
             }
         }
 
-        // Write the new file
-        new_conf.write_to_file(&new_path)
-            .map_err(|e| IdentityError::IniError(e.to_string()))?;
+        // Write the new file as a copy of the original, so other keys and sections survive
+        best_identity.write_counter_from(original_path, &new_path)?;
 
         self.last_save = Instant::now();
-        Ok(new_path_str)
+        Ok((new_path_str, removed_files))
     }
+}
 
-    /// Run the improvement search, calling the callback whenever a new level is found
+impl SearchHooks for ImproverFiles {
+    type Error = IdentityError;
+
+    fn started(&self, event: &mut SearchStarted) {
+        event.source = Some(self.original_file_path.clone());
+        event.resumed_from = self.resumed.then(|| (self.checkpoint_path.clone(), self.resumed_hashes));
+    }
+
+    /// Save immediately when we find a new level
+    fn new_level<H: SecurityLevelHasher>(&mut self, search: &mut OmegaSearch<H>, event: &mut NewLevelFound) -> Result<(), IdentityError> {
+        let (saved_path, removed_files) = self.save_progress(&search.best())?;
+        let mut best_identity = self.identity.clone();
+        best_identity.counter = event.counter;
+        event.identity = Some(best_identity.to_identity_string());
+        event.saved_to = Some(saved_path);
+        event.removed_files = removed_files;
+        Ok(())
+    }
+
+    fn batch_completed<H: SecurityLevelHasher>(&mut self, search: &mut OmegaSearch<H>) -> Result<(), IdentityError> {
+        if self.last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+            self.save_checkpoint(search)?;
+        }
+        Ok(())
+    }
+
+    fn stopped<H: SecurityLevelHasher>(&mut self, search: &mut OmegaSearch<H>) -> Result<(), IdentityError> {
+        self.save_checkpoint(search)
+    }
+}
+
+/// Work a search does besides reporting to its observer (level files, checkpoints)
+///
+/// [`OmegaSearch::run`] calls these at fixed points of the search loop, so every kind
+/// of search shares one loop.
+trait SearchHooks {
+    type Error;
+
+    /// Fill in the parts of the start event only the caller knows
+    fn started(&self, _event: &mut SearchStarted) {}
+
+    /// A new best level was found and is about to be reported
+    fn new_level<H: SecurityLevelHasher>(&mut self, _search: &mut OmegaSearch<H>, _event: &mut NewLevelFound) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// A batch was checked and the search position moved past it
+    fn batch_completed<H: SecurityLevelHasher>(&mut self, _search: &mut OmegaSearch<H>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// The search stopped (target reached, cancelled or exhausted)
+    fn stopped<H: SecurityLevelHasher>(&mut self, _search: &mut OmegaSearch<H>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Hooks of a bare [`OmegaSearch`]: nothing besides the observer
+struct NoHooks;
+
+impl SearchHooks for NoHooks {
+    type Error = Infallible;
+}
+
+/// Counter search that only needs the public key (omega)
+///
+/// Unlike [`LevelImprover`], no private key or identity file is involved, so the search
+/// can run on machines that must not see secret keys. It reports the best counter, which
+/// the key owner then puts into their identity (`Ts3Identity::counter`).
+pub struct OmegaSearch<H: SecurityLevelHasher> {
+    omega: String,
+    counter: u64, // Counter the search started from
+    hasher: H,
+    best_level: u8,
+    best_counter: u64,
+    current_counter: Option<u64>, // None once the counter space is exhausted
+    hashes_checked: u64,
+    start_time: Instant,
+    batch_size: usize,
+    stop_token: StopToken,
+    shard: Shard,
+    observer: Box<dyn ProgressObserver + Send>,
+}
+
+impl<H: SecurityLevelHasher> OmegaSearch<H> {
+    /// Search counters after `counter` for `omega` with default batch size
+    #[allow(dead_code)] // Public API method
+    pub fn new(omega: &str, counter: u64, hasher: H) -> Self {
+        Self::with_batch_size(omega, counter, hasher, DEFAULT_BATCH_SIZE)
+    }
+
+    /// Search counters after `counter` for `omega` with a custom batch size
+    pub fn with_batch_size(omega: &str, counter: u64, hasher: H, batch_size: usize) -> Self {
+        let omega = omega.trim().to_string();
+        let current_level = verify_security_level(&omega, counter);
+
+        Self {
+            omega,
+            counter,
+            hasher,
+            best_level: current_level,
+            best_counter: counter,
+            current_counter: counter.checked_add(1),
+            hashes_checked: 0,
+            start_time: Instant::now(),
+            batch_size,
+            stop_token: StopToken::new(),
+            shard: Shard::ALL,
+            observer: Box::new(TerminalObserver::new()),
+        }
+    }
+
+    /// Only search the counters of `shard`, starting at its first counter after `counter`
+    pub fn set_shard(&mut self, shard: Shard) {
+        self.shard = shard;
+        self.current_counter = self.current_counter.and_then(|counter| shard.first_at_or_after(counter));
+    }
+
+    /// Replace the observer receiving progress events (terminal output by default)
+    pub fn set_observer<O: ProgressObserver + Send + 'static>(&mut self, observer: O) {
+        self.observer = Box::new(observer);
+    }
+
+    /// Use `stop_token` to cancel the search from elsewhere
+    pub fn set_stop_token(&mut self, stop_token: StopToken) {
+        self.stop_token = stop_token;
+    }
+
+    /// Best counter and level found so far (the starting counter until something better turns up)
+    pub fn best(&self) -> LevelSearchResult {
+        LevelSearchResult { counter: self.best_counter, level: self.best_level }
+    }
+
+    /// Get statistics about the search
+    pub fn get_statistics(&self) -> ImprovementStatistics {
+        ImprovementStatistics {
+            hashes_checked: self.hashes_checked,
+            elapsed_secs: self.start_time.elapsed().as_secs_f64(),
+        }
+    }
+
+    /// Run the search, calling the callback whenever a new level is found
     /// The callback should return `false` to stop the search, or `true` to continue
-    pub fn improve<F>(&mut self, mut on_new_level: F) -> Result<(), IdentityError>
+    ///
+    /// The stop token is checked between batches. A cancelled search can be continued
+    /// by calling `search` again.
+    pub fn search<F>(&mut self, on_new_level: F) -> SearchOutcome
     where
         F: FnMut(&LevelSearchResult) -> bool,
     {
-        println!("\nStarting level improvement search...");
-        println!("Press Ctrl+C to stop\n");
-
-        let mut last_print = Instant::now();
-        let mut should_continue = true;
+        let Ok(outcome) = self.run(&mut NoHooks, on_new_level);
+        outcome
+    }
 
-        loop {
-            if !should_continue {
-                return Ok(());
+    /// The search loop shared by [`OmegaSearch::search`] and [`LevelImprover::improve`]
+    fn run<K, F>(&mut self, hooks: &mut K, mut on_new_level: F) -> Result<SearchOutcome, K::Error>
+    where
+        K: SearchHooks,
+        F: FnMut(&LevelSearchResult) -> bool,
+    {
+        let mut started = SearchStarted {
+            source: None,
+            public_key: self.omega.clone(),
+            counter: self.counter,
+            security_level: verify_security_level(&self.omega, self.counter),
+            next_counter: self.current_counter.unwrap_or(u64::MAX),
+            best_level: self.best_level,
+            best_counter: self.best_counter,
+            hasher: self.hasher.name().to_string(),
+            batch_size: self.batch_size,
+            resumed_from: None,
+        };
+        hooks.started(&mut started);
+        self.observer.on_start(&started);
+
+        let outcome = loop {
+            let Some(batch_start) = self.current_counter else {
+                break SearchOutcome::Exhausted;
+            };
+
+            if self.stop_token.is_cancelled() {
+                break SearchOutcome::Cancelled;
             }
 
-            // Prepare batch of counters to check
-            let counters: Vec<u64> = (self.current_counter..self.current_counter + self.batch_size as u64).collect();
+            // Prepare batch of counters to check (the last one stops at u64::MAX)
+            let (batch, next_counter) = self.shard.batch(batch_start, self.batch_size);
+            let counters: Vec<u64> = batch.collect();
 
             // Process entire batch at once (parallel on CPU, batched on GPU)
-            let omega = self.identity.public_key_base64();
-            let levels = self.hasher.calculate_levels_batch(&omega, &counters);
-
+            let levels = self.hasher.calculate_levels_batch(&self.omega, &counters);
             self.hashes_checked += counters.len() as u64;
 
-            // Check for improved levels in batch results
-            for (i, &level) in levels.iter().enumerate() {
-                let counter = counters[i];
+            let mut stop_at = None;
+            for (&counter, &level) in counters.iter().zip(&levels) {
+                if level <= self.best_level {
+                    continue;
+                }
+                self.best_level = level;
+                self.best_counter = counter;
+
+                let mut event = NewLevelFound {
+                    counter,
+                    level,
+                    hashes_checked: self.hashes_checked,
+                    identity: None,
+                    saved_to: None,
+                    removed_files: Vec::new(),
+                };
+                hooks.new_level(self, &mut event)?;
+                self.observer.on_new_level(&event);
+
+                if !on_new_level(&self.best()) {
+                    stop_at = Some(counter);
+                    break;
+                }
+            }
 
-                if level > self.best_level {
-                    self.best_level = level;
-                    self.best_counter = counter;
+            if let Some(counter) = stop_at {
+                // Everything up to and including this counter has been checked
+                self.current_counter = counter.checked_add(1);
+                break SearchOutcome::TargetReached(self.best());
+            }
 
-                    let result = LevelSearchResult {
-                        counter: self.best_counter,
-                        level: self.best_level,
-                    };
+            self.current_counter = next_counter;
+            hooks.batch_completed(self)?;
+
+            self.observer.on_batch_completed(&BatchCompleted {
+                best_level: self.best_level,
+                best_counter: self.best_counter,
+                next_counter: self.current_counter.unwrap_or(u64::MAX),
+                hashes_checked: self.hashes_checked,
+                elapsed_secs: self.start_time.elapsed().as_secs_f64(),
+            });
+        };
+        hooks.stopped(self)?;
+
+        let stats = self.get_statistics();
+        self.observer.on_finish(&SearchFinished {
+            outcome: outcome.clone(),
+            best_level: self.best_level,
+            best_counter: self.best_counter,
+            hashes_checked: stats.hashes_checked,
+            elapsed_secs: stats.elapsed_secs,
+        });
+
+        Ok(outcome)
+    }
+}
 
-                    println!("\n🎉 NEW LEVEL FOUND!");
-                    println!("   Counter: {}", result.counter);
-                    println!("   Level: {}", result.level);
-                    println!("   Hashes checked: {}", self.hashes_checked);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::{temp_test_dir, TEST_PUBLIC_KEY};
+    use crate::hashers::CpuHasher;
+    use crate::shard::SHARD_BLOCK_SIZE;
+    use crate::observer::{ChannelObserver, NoopObserver, ProgressEvent};
+    use std::fs;
+
+    /// Copy the level 8 test identity into a fresh temporary directory
+    fn temp_identity(name: &str) -> PathBuf {
+        let dir = temp_test_dir(&format!("improver-{}", name));
+        let path = dir.join("identity.ini");
+        fs::copy("inis/level8.ini", &path).unwrap();
+        path
+    }
 
-                    // Call the callback and check if we should continue
-                    should_continue = on_new_level(&result);
+    /// Temporary identity whose stored counter is `counter`
+    fn temp_identity_at(name: &str, counter: u64) -> PathBuf {
+        let path = temp_identity(name);
+        let mut identity = Ts3Identity::from_file(&path).unwrap();
+        identity.counter = counter;
+        identity.write_counter_to(&path).unwrap();
+        path
+    }
 
-                    // Save immediately when we find a new level
-                    let saved_path = self.save_progress()?;
-                    println!("   ✓ Progress saved to {}\n", saved_path);
+    #[test]
+    fn test_existing_checkpoint_not_overwritten() {
+        let path = temp_identity("existing-checkpoint");
+        let mut improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        improver.set_observer(NoopObserver);
+        assert!(improver.ensure_no_checkpoint().is_ok());
+        improver.improve(|result| result.level < 12).unwrap();
+
+        // A second run of the same identity must not silently start over
+        let improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        let existing = improver.ensure_no_checkpoint();
+        let mut resumed = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        let resume = resumed.resume_from_checkpoint();
+
+        // A checkpoint of another key is no search of this identity
+        let mut other = Checkpoint::load(improver.checkpoint_path()).unwrap();
+        other.public_key = "c29tZSBvdGhlciBrZXk=".to_string();
+        other.save(improver.checkpoint_path()).unwrap();
+        let other_key = improver.ensure_no_checkpoint();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert!(matches!(existing, Err(IdentityError::CheckpointError(CheckpointError::AlreadyExists(_)))));
+        assert!(resume.is_ok());
+        assert!(other_key.is_ok());
+    }
 
-                    if !should_continue {
-                        return Ok(());
-                    }
-                }
-            }
+    #[test]
+    fn test_counter_batch() {
+        assert_eq!(counter_batch(0, 10), (0..=9, Some(10)));
+        assert_eq!(counter_batch(u64::MAX - 10, 10), (u64::MAX - 10..=u64::MAX - 1, Some(u64::MAX)));
+        assert_eq!(counter_batch(u64::MAX - 9, 10), (u64::MAX - 9..=u64::MAX, None));
+        assert_eq!(counter_batch(u64::MAX - 5, 10), (u64::MAX - 5..=u64::MAX, None));
+        assert_eq!(counter_batch(u64::MAX, 1), (u64::MAX..=u64::MAX, None));
+    }
 
-            self.current_counter += self.batch_size as u64;
+    #[test]
+    fn test_exhausted_near_u64_max() {
+        let path = temp_identity_at("exhausted", u64::MAX - 251);
+        let mut improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        let (observer, events) = ChannelObserver::channel();
+        improver.set_observer(observer);
+
+        let outcome = improver.improve(|_| true).unwrap();
+        let checkpoint = Checkpoint::load(improver.checkpoint_path()).unwrap();
+        drop(improver);
+        let events: Vec<ProgressEvent> = events.into_iter().collect();
+
+        // Resuming the exhausted search has nothing left to check
+        let mut resumed = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        resumed.set_observer(NoopObserver);
+        resumed.resume_from_checkpoint().unwrap();
+        let resumed_outcome = resumed.improve(|_| true).unwrap();
+        let resumed_checkpoint = resumed.checkpoint();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        // Counters MAX-250..=MAX: two full batches and a final one capped at 51 counters
+        assert_eq!(outcome, SearchOutcome::Exhausted);
+        assert_eq!(checkpoint.hashes_checked, 251);
+        assert_eq!(checkpoint.next_counter, None);
+        assert_eq!(resumed_outcome, SearchOutcome::Exhausted);
+        assert_eq!(resumed_checkpoint.hashes_checked, 251);
+
+        let next_counters: Vec<u64> = events.iter()
+            .filter_map(|event| match event {
+                ProgressEvent::BatchCompleted(batch) => Some(batch.next_counter),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(next_counters, vec![u64::MAX - 150, u64::MAX - 50, u64::MAX]);
+
+        // Best result is the first counter beating the stored one, up to and including u64::MAX
+        let public_key = checkpoint.public_key;
+        let start = (CpuHasher.calculate_level(&public_key, u64::MAX - 251), u64::MAX - 251);
+        let expected = (u64::MAX - 250..=u64::MAX)
+            .map(|counter| (CpuHasher.calculate_level(&public_key, counter), counter))
+            .fold(start, |best, (level, counter)| if level > best.0 { (level, counter) } else { best });
+        assert_eq!((checkpoint.best_level, checkpoint.best_counter), expected);
+    }
 
-            // Print progress every second
-            if last_print.elapsed() >= Duration::from_secs(1) {
-                helpers::print_progress(self.best_level, self.current_counter, self.hashes_checked, self.start_time);
-                last_print = Instant::now();
-            }
-        }
+    #[test]
+    fn test_exhausted_at_u64_max() {
+        let path = temp_identity_at("at-max", u64::MAX);
+        let mut improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        improver.set_observer(NoopObserver);
+
+        let outcome = improver.improve(|_| true).unwrap();
+        let checkpoint = Checkpoint::load(improver.checkpoint_path()).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert_eq!(outcome, SearchOutcome::Exhausted);
+        assert_eq!(checkpoint.hashes_checked, 0);
+    }
+
+    #[test]
+    fn test_cancelled_before_search() {
+        let path = temp_identity("cancelled");
+        let mut improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        improver.set_observer(NoopObserver);
+        let stop_token = StopToken::new();
+        improver.set_stop_token(stop_token.clone());
+        stop_token.cancel();
+
+        let outcome = improver.improve(|_| true).unwrap();
+        let checkpoint = Checkpoint::load(improver.checkpoint_path()).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert_eq!(outcome, SearchOutcome::Cancelled);
+        assert_eq!(checkpoint.next_counter, Some(15));
+        assert_eq!(checkpoint.hashes_checked, 0);
+    }
+
+    #[test]
+    fn test_target_reached() {
+        let path = temp_identity("target");
+        let mut improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        improver.set_observer(NoopObserver);
+
+        let outcome = improver.improve(|result| result.level < 12).unwrap();
+        let checkpoint = Checkpoint::load(improver.checkpoint_path()).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert_eq!(outcome, SearchOutcome::TargetReached(LevelSearchResult { counter: 672, level: 12 }));
+        assert_eq!(checkpoint.next_counter, Some(673));
+        assert_eq!(checkpoint.best_level, 12);
+    }
+
+    #[test]
+    fn test_sharded_checkpoint_and_resume() {
+        let path = temp_identity("sharded");
+        let shard = Shard::new(2, 3).unwrap();
+        let mut improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        improver.set_observer(NoopObserver);
+        improver.set_shard(shard);
+        let stop_token = StopToken::new();
+        improver.set_stop_token(stop_token.clone());
+        stop_token.cancel();
+
+        assert_eq!(improver.improve(|_| true).unwrap(), SearchOutcome::Cancelled);
+        let checkpoint = Checkpoint::load(improver.checkpoint_path()).unwrap();
+        assert_eq!(checkpoint.shard, shard);
+        assert_eq!(checkpoint.next_counter, Some(SHARD_BLOCK_SIZE));
+        assert_eq!(improver.checkpoint_path(), path.with_file_name("identity.shard2of3.checkpoint"));
+
+        // Resuming needs the same shard, even if a checkpoint is copied over
+        let mut other = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        other.set_shard(Shard::new(1, 3).unwrap());
+        fs::copy(improver.checkpoint_path(), other.checkpoint_path()).unwrap();
+        let result = other.resume_from_checkpoint();
+        let mut same = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        same.set_shard(shard);
+        same.resume_from_checkpoint().unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert!(matches!(result, Err(IdentityError::CheckpointError(CheckpointError::ShardMismatch(..)))));
+        assert_eq!(same.checkpoint().next_counter, Some(SHARD_BLOCK_SIZE));
+    }
+
+    #[test]
+    fn test_shards_share_directory() {
+        let path = temp_identity("shards-side-by-side");
+        let dir = path.parent().unwrap().to_path_buf();
+
+        // Shard 1/2 finds levels 9, 12, 14 and 18 in the first block, keeping only the last file
+        let mut first = LevelImprover::with_batch_size(&path, CpuHasher, 1000).unwrap();
+        first.set_observer(NoopObserver);
+        first.set_shard(Shard::new(1, 2).unwrap());
+        first.improve(|result| result.level < 18).unwrap();
+
+        // Shard 2/2 runs in the same directory afterwards
+        let mut second = LevelImprover::with_batch_size(&path, CpuHasher, 1000).unwrap();
+        second.set_observer(NoopObserver);
+        second.set_shard(Shard::new(2, 2).unwrap());
+        second.improve(|result| result.level < 12).unwrap();
+
+        let mut files: Vec<String> = fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        let first_checkpoint = Checkpoint::load(first.checkpoint_path()).unwrap();
+        let second_checkpoint = Checkpoint::load(second.checkpoint_path()).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(files, vec![
+            "identity.ini",
+            "identity.shard1of2-18.ini",
+            "identity.shard1of2.checkpoint",
+            "identity.shard2of2-12.ini",
+            "identity.shard2of2.checkpoint",
+        ]);
+        assert_eq!(first_checkpoint.best_level, 18);
+        assert!(second_checkpoint.next_counter > Some(SHARD_BLOCK_SIZE));
+    }
+
+    #[test]
+    fn test_omega_search_sharded() {
+        // Shard 2/2 starts at the second block and only hashes counters it owns
+        let shard = Shard::new(2, 2).unwrap();
+        let mut search = OmegaSearch::with_batch_size(TEST_PUBLIC_KEY, 14, CpuHasher, 1000);
+        search.set_observer(NoopObserver);
+        search.set_shard(shard);
+
+        let SearchOutcome::TargetReached(found) = search.search(|result| result.level < 10) else {
+            panic!("expected to reach level 10");
+        };
+        assert!(found.counter >= SHARD_BLOCK_SIZE && shard.contains(found.counter));
+        assert_eq!(verify_security_level(TEST_PUBLIC_KEY, found.counter), found.level);
+        assert_eq!(search.get_statistics().hashes_checked, ((found.counter - SHARD_BLOCK_SIZE) / 1000 + 1) * 1000);
+    }
+
+    #[test]
+    fn test_omega_search_target_reached() {
+        let mut search = OmegaSearch::with_batch_size(TEST_PUBLIC_KEY, 14, CpuHasher, 100);
+        let (observer, events) = ChannelObserver::channel();
+        search.set_observer(observer);
+
+        let outcome = search.search(|result| result.level < 12);
+        drop(search);
+
+        assert_eq!(outcome, SearchOutcome::TargetReached(LevelSearchResult { counter: 672, level: 12 }));
+        let found: Vec<NewLevelFound> = events.into_iter()
+            .filter_map(|event| match event {
+                ProgressEvent::NewLevel(found) => Some(found),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(found.iter().map(|f| (f.counter, f.level)).collect::<Vec<_>>(), vec![(201, 9), (672, 12)]);
+        assert!(found.iter().all(|f| f.identity.is_none() && f.saved_to.is_none()));
+    }
+
+    #[test]
+    fn test_omega_search_cancel_and_continue() {
+        let stop_token = StopToken::new();
+        let mut search = OmegaSearch::with_batch_size(TEST_PUBLIC_KEY, 14, CpuHasher, 100);
+        search.set_observer(NoopObserver);
+        search.set_stop_token(stop_token.clone());
+
+        stop_token.cancel();
+        assert_eq!(search.search(|_| true), SearchOutcome::Cancelled);
+        assert_eq!(search.best(), LevelSearchResult { counter: 14, level: 8 });
+        assert_eq!(search.get_statistics().hashes_checked, 0);
+
+        search.set_stop_token(StopToken::new());
+        assert_eq!(search.search(|result| result.level < 9), SearchOutcome::TargetReached(LevelSearchResult { counter: 201, level: 9 }));
+        assert_eq!(search.get_statistics().hashes_checked, 200);
+    }
+
+    #[test]
+    fn test_omega_search_exhausted() {
+        let mut search = OmegaSearch::with_batch_size(TEST_PUBLIC_KEY, u64::MAX - 50, CpuHasher, 30);
+        search.set_observer(NoopObserver);
+
+        assert_eq!(search.search(|_| true), SearchOutcome::Exhausted);
+        assert_eq!(search.get_statistics().hashes_checked, 50);
     }
-}
 
+    #[test]
+    fn test_channel_observer_events() {
+        let path = temp_identity("observer");
+        let mut improver = LevelImprover::with_batch_size(&path, CpuHasher, 100).unwrap();
+        let (observer, events) = ChannelObserver::channel();
+        improver.set_observer(observer);
+
+        improver.improve(|result| result.level < 12).unwrap();
+        drop(improver);
+        let events: Vec<ProgressEvent> = events.into_iter().collect();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        let ProgressEvent::Started(started) = &events[0] else {
+            panic!("first event should be Started, got {:?}", events[0]);
+        };
+        assert_eq!((started.counter, started.security_level, started.next_counter), (14, 8, 15));
+
+        let levels: Vec<(u64, u8)> = events.iter()
+            .filter_map(|event| match event {
+                ProgressEvent::NewLevel(found) => Some((found.counter, found.level)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(levels, vec![(201, 9), (672, 12)]);
+        assert_eq!(events.iter().filter(|e| matches!(e, ProgressEvent::BatchCompleted(_))).count(), 6);
+        assert!(matches!(events[events.len() - 2], ProgressEvent::CheckpointSaved(_)));
+
+        let ProgressEvent::Finished(finished) = events.last().unwrap() else {
+            panic!("last event should be Finished");
+        };
+        assert_eq!(finished.outcome, SearchOutcome::TargetReached(LevelSearchResult { counter: 672, level: 12 }));
+        assert_eq!(finished.hashes_checked, 700);
+    }
+}
@@ -2,15 +2,34 @@
 //!
 //! This library provides CPU and GPU-accelerated SHA1 hashing
 //! for calculating TeamSpeak 3 identity security levels.
+//!
+//! GPU support (`CudaHasher`) requires the `cuda` cargo feature and the CUDA toolkit.
 
 #![deny(unsafe_code)]
 
+pub mod checkpoint;
+pub mod distributed;
+pub mod estimate;
 pub mod hashers;
 pub mod helpers;
 pub mod identity;
 pub mod level_improver;
+pub mod observer;
+pub mod output;
+pub mod scheduler;
+pub mod settings_db;
+pub mod shard;
+pub mod verify;
+pub mod work;
 
 // Re-export commonly used items
-pub use hashers::{CpuHasher, CudaHasher};
+pub use checkpoint::Checkpoint;
+pub use hashers::{CpuHasher, CpuMidstateHasher, CpuShaNiHasher, CpuSimdHasher};
+#[cfg(feature = "cuda")]
+pub use hashers::CudaHasher;
 pub use identity::Ts3Identity;
-pub use level_improver::{LevelImprover, SecurityLevelHasher};
+pub use level_improver::{LevelImprover, OmegaSearch, SearchOutcome, SecurityLevelHasher, StopToken};
+pub use observer::{ChannelObserver, NoopObserver, ProgressEvent, ProgressObserver, TerminalObserver};
+pub use output::OutputFormat;
+pub use shard::Shard;
+pub use verify::{meets_level, verify_security_level};
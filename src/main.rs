@@ -1,38 +1,173 @@
 #![deny(unsafe_code, unused)]
 
+mod checkpoint;
+mod distributed;
+mod estimate;
 mod identity;
 mod level_improver;
 mod hashers;
 mod helpers;
+mod observer;
+mod output;
+mod scheduler;
+mod settings_db;
+mod shard;
+mod verify;
+mod work;
 mod cli;
 
 use identity::Ts3Identity;
-use level_improver::{LevelImprover, SecurityLevelHasher};
-use hashers::{CpuHasher, CudaHasher};
-use helpers::{print_statistics, format_number};
+use level_improver::{
+    LevelImprover, LevelSearchResult, OmegaSearch, SearchOutcome, SecurityLevelHasher, StopToken,
+};
+use hashers::{CpuHasher, CpuMidstateHasher, CpuShaNiHasher, CpuSimdHasher};
+#[cfg(feature = "cuda")]
+use hashers::CudaHasher;
+use helpers::{print_statistics, format_duration, format_hashrate, format_number};
 use cli::{Cli, Command, HasherMethod};
+use observer::{ProgressObserver, TerminalObserver};
+use output::{JsonObserver, OutputFormat, Record};
+use scheduler::IdentityReport;
+use settings_db::StoredIdentity;
+use shard::{Shard, SHARD_BLOCK_SIZE};
+use work::{WorkPackage, WorkResult};
+use distributed::{Coordinator, CoordinatorConfig, CoordinatorEvent, Worker, WorkerEvent};
 use clap::Parser;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
 
 fn main() {
     let cli = Cli::parse();
+    let output = cli.output;
 
     match cli.command {
-        Command::Decode { file, string } => {
-            decode_identity(file, string);
+        Command::Decode { db: Some(db), nickname: None, uid, .. } => {
+            list_db_identities(&db, uid, output);
         }
-        Command::Increase { file, string, target, method, batch_size, cuda_threads, cuda_shared_mem } => {
-            increase_level(file, string, target, method, batch_size, cuda_threads, cuda_shared_mem);
+        Command::Decode { file, string, db, nickname, uid } => {
+            let db_identity = db.zip(nickname);
+            decode_identity(file, string, db_identity, uid, output);
         }
+        Command::Increase {
+            omega: Some(omega), counter: Some(counter), target,
+            method, batch_size, cuda_threads, cuda_shared_mem, shard, ..
+        } => {
+            let shard = shard.unwrap_or_default();
+            increase_omega(&omega, counter, target, method, batch_size, cuda_threads, cuda_shared_mem, shard, output);
+        }
+        Command::Increase {
+            file, string, db, nickname, target,
+            method, batch_size, cuda_threads, cuda_shared_mem, resume, fresh, export_db, shard, ..
+        } => {
+            let db_identity = db.zip(nickname);
+            let shard = shard.unwrap_or_default();
+            increase_level(
+                file, string, db_identity, target,
+                method, batch_size, cuda_threads, cuda_shared_mem, resume, fresh, export_db, shard, output,
+            );
+        }
+        Command::IncreaseAll { dir, manifest, target, stages, method, batch_size, cuda_threads, cuda_shared_mem } => {
+            increase_all(dir, manifest, target, &stages, method, batch_size, cuda_threads, cuda_shared_mem, output);
+        }
+        Command::ExportWork { file, target, out } => {
+            export_work(&file, target, out, output);
+        }
+        Command::RunWork { package, out, method, batch_size, cuda_threads, cuda_shared_mem } => {
+            run_work(&package, out, method, batch_size, cuda_threads, cuda_shared_mem, output);
+        }
+        Command::ImportResult { file, result } => {
+            import_result(&file, &result, output);
+        }
+        Command::Merge { file, shards } => {
+            merge_shards(&file, &shards, output);
+        }
+        Command::Coordinate { omega, counter, target, listen, range_size, timeout, evidence_level, sample_size, state } => {
+            coordinate(&omega, counter, target, &listen, range_size, timeout, evidence_level, sample_size, &state, output);
+        }
+        Command::Worker { connect, name, method, batch_size, cuda_threads, cuda_shared_mem } => {
+            run_worker(&connect, name, method, batch_size, cuda_threads, cuda_shared_mem, output);
+        }
+        Command::Verify { omega, counter, uid, min_level } => {
+            verify_proof(&omega, counter, uid, min_level, output);
+        }
+        Command::Estimate {
+            file, current, target, hashrate, method, calibration_secs,
+            batch_size, cuda_threads, cuda_shared_mem,
+        } => {
+            estimate_time(
+                file, current, target, hashrate, method, calibration_secs,
+                batch_size, cuda_threads, cuda_shared_mem, output,
+            );
+        }
+        Command::Generate {
+            file, name, nickname, phonetic_nickname, force,
+            target, method, batch_size, cuda_threads, cuda_shared_mem,
+        } => {
+            generate_identity(&file, &name, &nickname, &phonetic_nickname, force, output);
+            if let Some(target) = target {
+                if output == OutputFormat::Human {
+                    println!();
+                }
+                increase_level(
+                    Some(file), None, None, target,
+                    method, batch_size, cuda_threads, cuda_shared_mem, false, false, None, Shard::ALL, output,
+                );
+            }
+        }
+    }
+}
+
+/// Emit the identity fields as a JSON record
+fn emit_identity(identity: &Ts3Identity, uid_matches: Option<bool>) {
+    output::emit(&Record::Identity {
+        uid: identity.uid(),
+        counter: identity.counter,
+        security_level: identity.security_level(),
+        public_key: identity.public_key_base64(),
+        nickname: identity.nickname.clone(),
+        uid_matches,
+    });
+}
+
+fn generate_identity(file: &str, name: &str, nickname: &str, phonetic_nickname: &str, force: bool, output: OutputFormat) {
+    if !force && std::path::Path::new(file).exists() {
+        eprintln!("❌ Error: '{}' already exists (use --force to overwrite)", file);
+        std::process::exit(1);
+    }
+
+    let mut identity = Ts3Identity::generate();
+    identity.name = name.to_string();
+    identity.nickname = nickname.to_string();
+    identity.phonetic_nickname = phonetic_nickname.to_string();
+
+    if let Err(e) = identity.write_to_file(file) {
+        eprintln!("❌ Error writing identity file '{}': {}", file, e);
+        std::process::exit(1);
+    }
+
+    if output == OutputFormat::Json {
+        emit_identity(&identity, None);
+        return;
     }
+
+    println!("✨ Generated new identity: {}", file);
+    println!("  Nickname:       {}", nickname);
+    println!("  Security Level: {}", identity.security_level());
+    println!("  Public Key:     {}", identity.public_key_base64());
 }
 
-fn decode_identity(file: Option<String>, string: Option<String>) {
+fn decode_identity(
+    file: Option<String>,
+    string: Option<String>,
+    db_identity: Option<(String, String)>,
+    expected_uid: Option<String>,
+    output: OutputFormat,
+) {
+    let human = output == OutputFormat::Human;
     let identity = if let Some(file_path) = file {
         match Ts3Identity::from_file(&file_path) {
             Ok(id) => {
-                println!("📄 Loaded from file: {}\n", file_path);
+                if human {
+                    println!("📄 Loaded from file: {}\n", file_path);
+                }
                 id
             }
             Err(e) => {
@@ -43,7 +178,9 @@ fn decode_identity(file: Option<String>, string: Option<String>) {
     } else if let Some(identity_str) = string {
         match Ts3Identity::parse_identity(&identity_str) {
             Ok(id) => {
-                println!("📝 Parsed from string\n");
+                if human {
+                    println!("📝 Parsed from string\n");
+                }
                 id
             }
             Err(e) => {
@@ -51,31 +188,682 @@ fn decode_identity(file: Option<String>, string: Option<String>) {
                 std::process::exit(1);
             }
         }
+    } else if let Some((db, nickname)) = &db_identity {
+        let stored = load_db_identity(db, nickname);
+        if human {
+            println!("🗄️  Loaded '{}' from {}\n", nickname, db);
+        }
+        stored.identity
     } else {
-        eprintln!("❌ Error: Must provide either --file or --string");
+        eprintln!("❌ Error: Must provide either --file, --string or --db");
         std::process::exit(1);
     };
 
+    if !human {
+        let uid_matches = expected_uid.as_deref().map(|uid| identity.uid() == uid.trim());
+        emit_identity(&identity, uid_matches);
+        if uid_matches == Some(false) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Display identity information
     println!("Identity Information:");
+    println!("  UID:            {}", identity.uid());
     println!("  Counter:        {}", identity.counter);
     println!("  Security Level: {}", identity.security_level());
     println!("  Public Key:     {}", identity.public_key_base64());
     println!("\n💡 Proof of Work:");
     println!("  This identity proves ~{:.0} SHA-1 hashes were computed.",
              2_f64.powi(identity.security_level() as i32));
+
+    if let Some(expected_uid) = expected_uid {
+        let expected_uid = expected_uid.trim();
+        if identity.uid() == expected_uid {
+            println!("\n✅ UID matches: {}", expected_uid);
+        } else {
+            println!("\n❌ UID does not match: expected {}, identity has {}", expected_uid, identity.uid());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Search a better counter for a bare public key (no private key on this machine)
+#[allow(clippy::too_many_arguments)] // Mirrors the CLI arguments
+fn increase_omega(
+    omega: &str,
+    counter: u64,
+    target_level: u8,
+    method: HasherMethod,
+    batch_size: Option<usize>,
+    cuda_threads: Option<usize>,
+    cuda_shared_mem: Option<usize>,
+    shard: Shard,
+    output: OutputFormat,
+) {
+    use base64::Engine;
+
+    let human = output == OutputFormat::Human;
+    let omega = omega.trim();
+    if base64::engine::general_purpose::STANDARD.decode(omega).is_err() {
+        eprintln!("❌ Error: --omega must be the base64 public key (as shown by decode)");
+        std::process::exit(1);
+    }
+
+    let current_level = verify::verify_security_level(omega, counter);
+    if human {
+        println!("🚀 TeamSpeak 3 Security Level Improver\n");
+        println!("🔑 Public key only (UID {})", identity::public_key_uid(omega));
+        println!("📊 Current level: {} (counter {})", current_level, counter);
+        println!("🎯 Target level:  {}", target_level);
+        print_shard(shard);
+    }
+
+    if current_level >= target_level {
+        if human {
+            println!("\n✅ Counter already at or above target level!");
+        } else {
+            output::emit(&Record::Finished {
+                outcome: "target_reached",
+                target_reached: true,
+                best_level: current_level,
+                best_counter: counter,
+            });
+        }
+        return;
+    }
+
+    let stop_token = install_stop_handler(human);
+    let batch_size = batch_size.unwrap_or(default_batch_size(method));
+    let hasher = create_hasher(method, cuda_threads, cuda_shared_mem, human);
+
+    let mut search = OmegaSearch::with_batch_size(omega, counter, hasher, batch_size);
+    search.set_shard(shard);
+    search.set_stop_token(stop_token);
+    search.set_observer(make_observer(output, target_level));
+    let outcome = search.search(|result| result.level < target_level);
+
+    // The JSON observer already emitted the finished record with the best counter
+    if !human {
+        return;
+    }
+
+    let best = search.best();
+    match outcome {
+        SearchOutcome::TargetReached(_) => {
+            println!("\n🎉 Successfully reached target security level {}!", target_level);
+        }
+        SearchOutcome::Cancelled => {
+            println!("\n⚠️  Stopped before reaching target level {}.", target_level);
+        }
+        SearchOutcome::Exhausted => {
+            println!("\n⚠️  Counter space exhausted before reaching target level {}.", target_level);
+        }
+    }
+    if best.counter == counter {
+        println!("\nℹ️  No better counter found");
+    } else {
+        println!("\n🏆 Winning counter: {} (level {})", best.counter, best.level);
+        println!("   Put it in front of the 'V' in the identity string: identity=\"{}V...\"", best.counter);
+    }
+
+    let stats = search.get_statistics();
+    print_statistics(stats.hashes_checked, stats.elapsed_secs);
+}
+
+/// Write a work package for an identity, so it can be improved without its private key
+fn export_work(file: &str, target_level: u8, out: Option<String>, output: OutputFormat) {
+    let identity = match Ts3Identity::from_file(file) {
+        Ok(identity) => identity,
+        Err(e) => {
+            eprintln!("❌ Error loading file '{}': {}", file, e);
+            std::process::exit(1);
+        }
+    };
+
+    let package = WorkPackage::for_identity(&identity, target_level);
+    let path = out.unwrap_or_else(|| WorkPackage::path_for(file).to_string_lossy().to_string());
+    if let Err(e) = package.save(&path) {
+        eprintln!("❌ Error writing work package '{}': {}", path, e);
+        std::process::exit(1);
+    }
+
+    if output == OutputFormat::Json {
+        output::emit(&Record::WorkExported {
+            path,
+            uid: package.fingerprint,
+            counter: package.counter,
+            target_level,
+        });
+        return;
+    }
+
+    println!("📦 Work package written: {}", path);
+    println!("  UID:            {}", package.fingerprint);
+    println!("  Counter:        {} (level {})", package.counter, identity.security_level());
+    println!("  Target level:   {}", target_level);
+    println!("\n💡 The package holds no private key. On the worker run:");
+    println!("   run-work --package {}", path);
 }
 
+/// Search a work package's public key and write the best counter to a result file
+#[allow(clippy::too_many_arguments)] // Mirrors the CLI arguments
+fn run_work(
+    package_path: &str,
+    out: Option<String>,
+    method: HasherMethod,
+    batch_size: Option<usize>,
+    cuda_threads: Option<usize>,
+    cuda_shared_mem: Option<usize>,
+    output: OutputFormat,
+) {
+    let human = output == OutputFormat::Human;
+    let package = match WorkPackage::load(package_path) {
+        Ok(package) => package,
+        Err(e) => {
+            eprintln!("❌ Error loading work package '{}': {}", package_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    if human {
+        println!("🚀 TeamSpeak 3 Security Level Improver\n");
+        println!("📦 Work package: {} (UID {})", package_path, package.fingerprint);
+        println!("📊 Current level: {} (counter {})", verify::verify_security_level(&package.public_key, package.counter), package.counter);
+        println!("🎯 Target level:  {}", package.target_level);
+    }
+
+    let stop_token = install_stop_handler(human);
+    let batch_size = batch_size.unwrap_or(default_batch_size(method));
+    let hasher = create_hasher(method, cuda_threads, cuda_shared_mem, human);
+    let hasher_name = hasher.name().to_string();
+
+    let mut search = OmegaSearch::with_batch_size(&package.public_key, package.counter, hasher, batch_size);
+    if search.best().level < package.target_level {
+        search.set_stop_token(stop_token);
+        search.set_observer(make_observer(output, package.target_level));
+        search.search(|result| result.level < package.target_level);
+    }
+
+    // Cancelled and exhausted runs still hand back the best counter they found
+    let stats = search.get_statistics();
+    let result = WorkResult::new(&package, search.best(), stats.hashes_checked, stats.elapsed_secs, &hasher_name);
+    let path = out.unwrap_or_else(|| WorkResult::path_for(package_path).to_string_lossy().to_string());
+    if let Err(e) = result.save(&path) {
+        eprintln!("❌ Error writing result '{}': {}", path, e);
+        std::process::exit(1);
+    }
+
+    if !human {
+        output::emit(&Record::WorkResultWritten {
+            path,
+            counter: result.counter,
+            level: result.level,
+            target_reached: result.target_reached(),
+        });
+        return;
+    }
+
+    if result.target_reached() {
+        println!("\n🎉 Reached target security level {}!", package.target_level);
+    } else {
+        println!("\n⚠️  Stopped before reaching target level {}.", package.target_level);
+    }
+    println!("\n📄 Result written: {} (counter {}, level {})", path, result.counter, result.level);
+    println!("   Bring it back and run: import-result --file <identity.ini> --result {}", path);
+    print_statistics(stats.hashes_checked, stats.elapsed_secs);
+}
+
+/// Validate a worker's result and write its counter into the identity file
+fn import_result(file: &str, result_path: &str, output: OutputFormat) {
+    let mut identity = match Ts3Identity::from_file(file) {
+        Ok(identity) => identity,
+        Err(e) => {
+            eprintln!("❌ Error loading file '{}': {}", file, e);
+            std::process::exit(1);
+        }
+    };
+    let result = match WorkResult::load(result_path) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("❌ Error loading result '{}': {}", result_path, e);
+            std::process::exit(1);
+        }
+    };
+
+    let previous_level = identity.security_level();
+    let updated = match result.apply_to(&mut identity) {
+        Ok(updated) => updated,
+        Err(e) => {
+            eprintln!("❌ Result rejected: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if updated && let Err(e) = identity.write_counter_to(file) {
+        eprintln!("❌ Error writing identity file '{}': {}", file, e);
+        std::process::exit(1);
+    }
+
+    if output == OutputFormat::Json {
+        output::emit(&Record::ResultImported {
+            file: file.to_string(),
+            counter: result.counter,
+            level: result.level,
+            updated,
+        });
+        return;
+    }
+
+    println!("✅ Result verified: counter {} has level {} ({} hashes on {})",
+             result.counter, result.level, format_number(result.hashes_checked), result.hasher);
+    if updated {
+        println!("💾 Updated {}: level {} → {}", file, previous_level, result.level);
+    } else {
+        println!("ℹ️  {} already has level {}, left unchanged", file, previous_level);
+    }
+}
+
+/// Print which part of the counter space a sharded search covers
+fn print_shard(shard: Shard) {
+    if !shard.is_all() {
+        println!("🧩 Shard:         {} (one in {} blocks of {} counters)",
+                 shard, shard.count(), format_number(SHARD_BLOCK_SIZE));
+    }
+}
+
+/// Re-verify the identity files of sharded runs and put the best counter into `file`
+///
+/// Exits with status 1 if none of the shard files belongs to the identity.
+fn merge_shards(file: &str, shard_files: &[String], output: OutputFormat) {
+    let human = output == OutputFormat::Human;
+    let mut identity = match Ts3Identity::from_file(file) {
+        Ok(identity) => identity,
+        Err(e) => {
+            eprintln!("❌ Error loading file '{}': {}", file, e);
+            std::process::exit(1);
+        }
+    };
+    let omega = identity.public_key_base64();
+    let previous_level = identity.security_level();
+
+    if human {
+        println!("🧩 Merging {} shard files into {} (level {}, counter {})\n",
+                 shard_files.len(), file, previous_level, identity.counter);
+    }
+
+    let mut best: Option<(&str, LevelSearchResult)> = None;
+    for path in shard_files {
+        let checked = Ts3Identity::from_file(path)
+            .map_err(|e| e.to_string())
+            .and_then(|shard_identity| {
+                if shard_identity.public_key_base64() == omega {
+                    Ok(shard_identity.counter)
+                } else {
+                    Err(format!("belongs to a different identity (UID {})", shard_identity.uid()))
+                }
+            });
+
+        // The level is always re-hashed; the level in the file name is only compared
+        let claimed_level = std::path::Path::new(path)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.rsplit_once('-'))
+            .and_then(|(_, level)| level.parse::<u8>().ok());
+        let (counter, level) = match &checked {
+            Ok(counter) => (Some(*counter), Some(verify::verify_security_level(&omega, *counter))),
+            Err(_) => (None, None),
+        };
+
+        if human {
+            match (&checked, counter, level) {
+                (Err(e), _, _) => println!("   ❌ {}: {}", path, e),
+                (Ok(_), Some(counter), Some(level)) if claimed_level.is_some_and(|claimed| claimed != level) => {
+                    println!("   ⚠️  {}: counter {} has level {}, not {} as the file name says",
+                             path, counter, level, claimed_level.unwrap_or_default());
+                }
+                (Ok(_), Some(counter), Some(level)) => println!("   ✅ {}: counter {}, level {}", path, counter, level),
+                _ => {}
+            }
+        } else {
+            output::emit(&Record::ShardChecked {
+                file: path.clone(),
+                counter,
+                level,
+                claimed_level,
+                error: checked.as_ref().err().cloned(),
+            });
+        }
+
+        if let (Some(counter), Some(level)) = (counter, level)
+            && best.as_ref().is_none_or(|(_, best)| level > best.level)
+        {
+            best = Some((path, LevelSearchResult { counter, level }));
+        }
+    }
+
+    let Some((source, best)) = best else {
+        eprintln!("❌ None of the shard files belongs to the identity in '{}'", file);
+        std::process::exit(1);
+    };
+
+    let updated = best.level > previous_level;
+    if updated {
+        identity.counter = best.counter;
+        if let Err(e) = identity.write_counter_to(file) {
+            eprintln!("❌ Error writing identity file '{}': {}", file, e);
+            std::process::exit(1);
+        }
+    }
+
+    if !human {
+        output::emit(&Record::ShardsMerged {
+            file: file.to_string(),
+            source: source.to_string(),
+            counter: best.counter,
+            level: best.level,
+            updated,
+        });
+        return;
+    }
+
+    println!("\n🏆 Best: counter {} (level {}) from {}", best.counter, best.level, source);
+    if updated {
+        println!("💾 Updated {}: level {} → {}", file, previous_level, best.level);
+    } else {
+        println!("ℹ️  {} already has level {}, left unchanged", file, previous_level);
+    }
+}
+
+/// Run a coordinator that hands out counter ranges of a public key to workers
+#[allow(clippy::too_many_arguments)] // Mirrors the CLI arguments
+fn coordinate(
+    omega: &str,
+    counter: u64,
+    target_level: u8,
+    listen: &str,
+    range_size: u64,
+    timeout_secs: u64,
+    evidence_level: u8,
+    sample_size: usize,
+    state_path: &str,
+    output: OutputFormat,
+) {
+    use base64::Engine;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, Instant};
+
+    let human = output == OutputFormat::Human;
+    let omega = omega.trim();
+    if base64::engine::general_purpose::STANDARD.decode(omega).is_err() {
+        eprintln!("❌ Error: --omega must be the base64 public key (as shown by decode)");
+        std::process::exit(1);
+    }
+
+    let mut config = CoordinatorConfig::new(omega, counter, target_level);
+    config.range_size = range_size.max(1);
+    config.worker_timeout = Duration::from_secs(timeout_secs.max(1));
+    config.evidence_level = evidence_level;
+    config.sample_size = sample_size;
+    config.state_path = Some(PathBuf::from(state_path));
+    let resumed = Path::new(state_path).exists();
+
+    let mut coordinator = match Coordinator::bind(listen, config) {
+        Ok(coordinator) => coordinator,
+        Err(e) => {
+            eprintln!("❌ Error starting coordinator on {}: {}", listen, e);
+            std::process::exit(1);
+        }
+    };
+    let listen = coordinator.local_addr().map(|a| a.to_string()).unwrap_or_else(|_| listen.to_string());
+    let state = coordinator.state();
+    let uid = identity::public_key_uid(omega);
+
+    if human {
+        println!("🚀 TeamSpeak 3 Security Level Coordinator\n");
+        println!("🔑 Public key only (UID {})", uid);
+        println!("📊 Current level: {} (counter {})", state.best_level, state.best_counter);
+        println!("🎯 Target level:  {}", target_level);
+        if resumed {
+            println!("📂 Resuming from {} ({} counters checked so far)", state_path, format_number(state.completed_counters() as u64));
+        }
+        println!("📡 Listening on {} (ranges of {} counters)", listen, format_number(range_size));
+        println!("🔍 Spot checks:   evidence from level {}, {} re-hashed per report", evidence_level, sample_size);
+        println!("   Start workers with: worker --connect <this host>:{}\n", listen.rsplit(':').next().unwrap_or_default());
+    } else {
+        output::emit(&Record::CoordinatorStarted {
+            listen,
+            uid,
+            counter,
+            target_level,
+            best_level: state.best_level,
+            resumed,
+        });
+    }
+
+    coordinator.set_stop_token(install_stop_handler(human));
+    coordinator.set_event_handler(move |event| {
+        if !human {
+            output::emit(&Record::coordinator_event(event));
+            return;
+        }
+        match event {
+            CoordinatorEvent::WorkerJoined { worker, hasher } => println!("👋 {} joined ({})", worker, hasher),
+            CoordinatorEvent::RangeAssigned { worker, range } => println!("📤 {} ← {}", worker, range),
+            CoordinatorEvent::RangeCompleted { worker, range } => println!("✅ {} finished {}", worker, range),
+            CoordinatorEvent::RangeReleased { worker, range } => println!("↩️  {} released {}", worker, range),
+            CoordinatorEvent::NewLevel { worker, counter, level, .. } => {
+                println!("🎉 New level {} at counter {} (found by {})", level, counter, worker);
+            }
+            CoordinatorEvent::HitRejected { worker, counter, claimed, actual } => {
+                println!("🚫 {} claimed level {} for counter {}, but it is {}", worker, claimed, counter, actual);
+            }
+            CoordinatorEvent::RangeFlagged { worker, range, reason } => {
+                println!("🚩 Range {} from {} flagged for rescanning: {}", range, worker, reason);
+            }
+            CoordinatorEvent::Status { workers, best_level, hashes_checked, hashes_per_sec, .. } => {
+                println!("📊 {} workers | best level {} | {} hashes | {}",
+                         workers, best_level, format_number(*hashes_checked), format_hashrate(*hashes_per_sec));
+            }
+        }
+    });
+
+    let start_time = Instant::now();
+    let outcome = match coordinator.run() {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            eprintln!("❌ Coordinator error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let state = coordinator.state();
+    let best = state.best();
+
+    if !human {
+        output::emit(&Record::Finished {
+            outcome: output::outcome_name(&outcome),
+            target_reached: matches!(outcome, SearchOutcome::TargetReached(_)),
+            best_level: best.level,
+            best_counter: best.counter,
+        });
+        return;
+    }
+
+    match outcome {
+        SearchOutcome::TargetReached(_) => {
+            println!("\n🎉 Successfully reached target security level {}!", target_level);
+        }
+        SearchOutcome::Cancelled => {
+            println!("\n⚠️  Stopped before reaching target level {}.", target_level);
+            println!("   Progress is saved in {}; run the same command again to continue.", state_path);
+        }
+        SearchOutcome::Exhausted => {
+            println!("\n⚠️  Counter space exhausted before reaching target level {}.", target_level);
+        }
+    }
+    if best.counter == counter {
+        println!("\nℹ️  No better counter found");
+    } else {
+        println!("\n🏆 Winning counter: {} (level {})", best.counter, best.level);
+        println!("   Put it in front of the 'V' in the identity string: identity=\"{}V...\"", best.counter);
+    }
+    print_statistics(state.hashes_checked, start_time.elapsed().as_secs_f64());
+}
+
+/// Connect to a coordinator and search the ranges it hands out
+fn run_worker(
+    connect: &str,
+    name: Option<String>,
+    method: HasherMethod,
+    batch_size: Option<usize>,
+    cuda_threads: Option<usize>,
+    cuda_shared_mem: Option<usize>,
+    output: OutputFormat,
+) {
+    use std::time::Instant;
+
+    let human = output == OutputFormat::Human;
+    let name = name
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .unwrap_or_else(|| format!("worker-{}", std::process::id()));
+
+    if human {
+        println!("🚀 TeamSpeak 3 Security Level Worker\n");
+        println!("📡 Coordinator: {} (as {})", connect, name);
+    }
+
+    let stop_token = install_stop_handler(human);
+    let batch_size = batch_size.unwrap_or(default_batch_size(method));
+    let hasher = create_hasher(method, cuda_threads, cuda_shared_mem, human);
+
+    let mut worker = Worker::with_batch_size(&name, hasher, batch_size);
+    worker.set_stop_token(stop_token);
+    let worker_name = name.clone();
+    worker.set_event_handler(move |event| match event {
+        WorkerEvent::RangeAssigned { range } if human => println!("📥 Searching {}", range),
+        WorkerEvent::RangeAssigned { range } => output::emit(&Record::RangeAssigned {
+            worker: worker_name.clone(),
+            start: range.start,
+            end: range.end,
+        }),
+        WorkerEvent::Hit { counter, level, .. } if human => println!("🎉 Level {} at counter {}", level, counter),
+        WorkerEvent::Hit { counter, level, hashes_checked } => output::emit(&Record::NewLevel {
+            counter: *counter,
+            level: *level,
+            hashes_checked: *hashes_checked,
+            identity: None,
+            saved_to: None,
+            worker: Some(worker_name.clone()),
+        }),
+        WorkerEvent::RangeCompleted { range, hashes, elapsed_secs } if human => {
+            println!("✅ Finished {} ({})", range, format_hashrate(*hashes as f64 / elapsed_secs.max(1e-9)));
+        }
+        WorkerEvent::RangeCompleted { range, .. } => output::emit(&Record::RangeCompleted {
+            worker: worker_name.clone(),
+            start: range.start,
+            end: range.end,
+        }),
+        WorkerEvent::RangeRevoked { range } if human => println!("↩️  Coordinator took back {}", range),
+        WorkerEvent::RangeRevoked { range } => output::emit(&Record::RangeReleased {
+            worker: worker_name.clone(),
+            start: range.start,
+            end: range.end,
+        }),
+    });
+
+    let start_time = Instant::now();
+    let summary = match worker.run(connect) {
+        Ok(summary) => summary,
+        Err(e) => {
+            eprintln!("❌ Worker error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let elapsed_secs = start_time.elapsed().as_secs_f64();
+
+    if !human {
+        output::emit(&Record::statistics(summary.hashes_checked, elapsed_secs));
+        return;
+    }
+
+    match summary.best {
+        Some(best) => println!("\n🏁 Search finished: best level {} at counter {}", best.level, best.counter),
+        None => println!("\n⚠️  Stopped; the coordinator will hand the rest of the range to another worker."),
+    }
+    println!("   {} ranges completed, {} hits reported", summary.ranges_completed, summary.hits);
+    print_statistics(summary.hashes_checked, elapsed_secs);
+}
+
+/// Check a claimed security level from the public key and counter alone
+///
+/// Exits with status 1 if the UID does not match or the level is below `min_level`.
+fn verify_proof(omega: &str, counter: u64, expected_uid: Option<String>, min_level: Option<u8>, output: OutputFormat) {
+    let omega = omega.trim();
+    let uid = identity::public_key_uid(omega);
+    let level = verify::verify_security_level(omega, counter);
+    let uid_matches = expected_uid.as_deref().map(|expected| expected.trim() == uid);
+    let meets_level = min_level.map(|min_level| verify::meets_level(omega, counter, min_level));
+    let passed = uid_matches != Some(false) && meets_level != Some(false);
+
+    if output == OutputFormat::Json {
+        output::emit(&Record::Verification {
+            uid,
+            counter,
+            security_level: level,
+            uid_matches,
+            min_level,
+            meets_level,
+        });
+    } else {
+        println!("🔍 Verification:");
+        println!("  UID:            {}", uid);
+        println!("  Counter:        {}", counter);
+        println!("  Security Level: {}", level);
+
+        if expected_uid.is_some() || min_level.is_some() {
+            println!();
+        }
+        if let Some(expected_uid) = &expected_uid {
+            if uid_matches == Some(true) {
+                println!("✅ UID matches: {}", expected_uid.trim());
+            } else {
+                println!("❌ UID does not match: expected {}, public key has {}", expected_uid.trim(), uid);
+            }
+        }
+        if let Some(min_level) = min_level {
+            if meets_level == Some(true) {
+                println!("✅ Meets minimum level {}", min_level);
+            } else {
+                println!("❌ Below minimum level {} (has {})", min_level, level);
+            }
+        }
+    }
+
+    if !passed {
+        std::process::exit(1);
+    }
+}
+
+#[allow(clippy::too_many_arguments)] // Mirrors the CLI arguments
 fn increase_level(
     file: Option<String>,
     string: Option<String>,
+    db_identity: Option<(String, String)>,
     target_level: u8,
     method: HasherMethod,
     batch_size: Option<usize>,
     cuda_threads: Option<usize>,
     cuda_shared_mem: Option<usize>,
+    resume: bool,
+    fresh: bool,
+    export_db: Option<String>,
+    shard: Shard,
+    output: OutputFormat,
 ) {
-    println!("🚀 TeamSpeak 3 Security Level Improver\n");
+    let human = output == OutputFormat::Human;
+    if human {
+        println!("🚀 TeamSpeak 3 Security Level Improver\n");
+    }
 
     // Determine input mode
     let (current_identity, input_source) = if let Some(file_path) = &file {
@@ -94,61 +882,419 @@ fn increase_level(
                 std::process::exit(1);
             }
         }
+    } else if let Some((db, nickname)) = &db_identity {
+        (load_db_identity(db, nickname).identity, format!("Settings database: {} ({})", db, nickname))
     } else {
-        eprintln!("❌ Error: Must provide either --file or --string");
+        eprintln!("❌ Error: Must provide either --file, --string or --db");
         std::process::exit(1);
     };
 
     let current_level = current_identity.security_level();
-    println!("📄 {}", input_source);
-    println!("📊 Current level: {}", current_level);
-    println!("🎯 Target level:  {}", target_level);
+    if human {
+        println!("📄 {}", input_source);
+        println!("📊 Current level: {}", current_level);
+        println!("🎯 Target level:  {}", target_level);
+        print_shard(shard);
+    }
+
+    if current_level >= target_level {
+        if human {
+            println!("\n✅ Identity already at or above target level!");
+        } else {
+            output::emit(&Record::Finished {
+                outcome: "target_reached",
+                target_reached: true,
+                best_level: current_level,
+                best_counter: current_identity.counter,
+            });
+        }
+        return;
+    }
+
+    // Setup Ctrl+C handler: the search loops stop (and flush their state) once the token is cancelled
+    let stop_token = install_stop_handler(human);
+    let batch_size = batch_size.unwrap_or(default_batch_size(method));
+    let hasher = create_hasher(method, cuda_threads, cuda_shared_mem, human);
+
+    let best = if let Some(file_path) = file {
+        run_improver_file(&file_path, target_level, hasher, batch_size, resume, fresh, shard, stop_token, output)
+    } else {
+        // Identity strings and settings.db entries are improved in memory (stdout only)
+        let identity_str = string.unwrap_or_else(|| current_identity.to_identity_string());
+        run_improver_string(&identity_str, target_level, hasher, batch_size, shard, stop_token, output)
+    };
+
+    if let Some(db) = export_db {
+        if best.level > current_level {
+            let mut improved = current_identity.clone();
+            improved.counter = best.counter;
+            export_to_db(&db, &improved, output);
+        } else if human {
+            println!("\nℹ️  No better counter found, {} left unchanged", db);
+        }
+    }
+}
+
+/// Write an improved identity's counter into a settings.db copy
+fn export_to_db(db: &str, identity: &Ts3Identity, output: OutputFormat) {
+    let update = match settings_db::update_counter(db, identity) {
+        Ok(update) => update,
+        Err(e) => {
+            eprintln!("❌ Error updating '{}': {}", db, e);
+            std::process::exit(1);
+        }
+    };
+
+    if output == OutputFormat::Json {
+        output::emit(&Record::DatabaseUpdated {
+            database: db.to_string(),
+            backup: update.backup_path.map(|path| path.display().to_string()),
+            counter: identity.counter,
+            level: identity.security_level(),
+            updated: update.updated.iter().map(|(stored, _)| stored.identity.name.clone()).collect(),
+        });
+        return;
+    }
+
+    if update.updated.is_empty() {
+        println!("\nℹ️  {} already stores this identity at level {} or higher", db, identity.security_level());
+        return;
+    }
+    println!("\n🗄️  Updated {}:", db);
+    if let Some(backup_path) = &update.backup_path {
+        println!("   Backup: {}", backup_path.display());
+    }
+    for (stored, previous_counter) in &update.updated {
+        println!("   #{} {} ({}): counter {} → {} (level {})",
+                 stored.index,
+                 stored.identity.name,
+                 stored.identity.nickname,
+                 previous_counter,
+                 stored.identity.counter,
+                 stored.identity.security_level());
+    }
+}
+
+/// Load one identity from a settings.db copy, exiting with an error if it is missing or ambiguous
+fn load_db_identity(db: &str, nickname: &str) -> StoredIdentity {
+    match settings_db::find_identity(db, nickname) {
+        Ok(stored) => stored,
+        Err(e) => {
+            eprintln!("❌ Error loading identity from '{}': {}", db, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// List every identity stored in a settings.db copy
+fn list_db_identities(db: &str, expected_uid: Option<String>, output: OutputFormat) {
+    let identities = match settings_db::read_identities(db) {
+        Ok(identities) => identities,
+        Err(e) => {
+            eprintln!("❌ Error reading '{}': {}", db, e);
+            std::process::exit(1);
+        }
+    };
+
+    // With --uid, mark the entries it matches and fail if there are none
+    let expected_uid = expected_uid.as_deref().map(str::trim);
+    let uid_matches = |stored: &StoredIdentity| expected_uid.map(|uid| stored.identity.uid() == uid);
+    let any_match = identities.iter().any(|stored| uid_matches(stored) == Some(true));
+
+    if output == OutputFormat::Json {
+        for stored in &identities {
+            emit_identity(&stored.identity, uid_matches(stored));
+        }
+        if expected_uid.is_some() && !any_match {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let column = |get: fn(&StoredIdentity) -> &str, title: &str| identities.iter()
+        .map(|stored| get(stored).chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(title.len());
+    let name_width = column(|stored| &stored.identity.name, "Name");
+    let nickname_width = column(|stored| &stored.identity.nickname, "Nickname");
+
+    println!("🗄️  Identities in {}:\n", db);
+    println!("   {:>2}  {:<name_width$}  {:<nickname_width$}  {:<28}  {:>5}", "#", "Name", "Nickname", "UID", "Level");
+    for stored in &identities {
+        println!("   {:>2}  {:<name_width$}  {:<nickname_width$}  {:<28}  {:>5}{}",
+                 stored.index,
+                 stored.identity.name,
+                 stored.identity.nickname,
+                 stored.identity.uid(),
+                 stored.identity.security_level(),
+                 if uid_matches(stored) == Some(true) { "  ✅" } else { "" });
+    }
+
+    if let Some(expected_uid) = expected_uid {
+        if any_match {
+            println!("\n✅ UID matches: {}", expected_uid);
+        } else {
+            println!("\n❌ UID does not match any identity: {}", expected_uid);
+            std::process::exit(1);
+        }
+    }
+    println!("\n💡 Use --db {} --nickname <NICKNAME or NAME> to pick one", db);
+}
+
+#[allow(clippy::too_many_arguments)] // Mirrors the CLI arguments
+fn increase_all(
+    dir: Option<String>,
+    manifest: Option<String>,
+    target_level: Option<u8>,
+    stages: &[u8],
+    method: HasherMethod,
+    batch_size: Option<usize>,
+    cuda_threads: Option<usize>,
+    cuda_shared_mem: Option<usize>,
+    output: OutputFormat,
+) {
+    let human = output == OutputFormat::Human;
+    if human {
+        println!("🚀 TeamSpeak 3 Security Level Improver (all identities)\n");
+    }
+
+    let entries = match (&dir, &manifest) {
+        (_, Some(manifest)) => scheduler::from_manifest(manifest, target_level),
+        (Some(dir), None) => scheduler::from_directory(dir, target_level.unwrap_or_default()),
+        (None, None) => {
+            eprintln!("❌ Error: Must provide either --dir or --manifest");
+            std::process::exit(1);
+        }
+    };
+    let entries = match entries {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("❌ Error: {}", e);
+            std::process::exit(1);
+        }
+    };
 
+    if human {
+        println!("📄 {} identities", entries.len());
+        for entry in &entries {
+            println!("   {} → level {} (priority {})", entry.path.display(), entry.target, entry.priority);
+        }
+        if !stages.is_empty() {
+            let stages: Vec<String> = stages.iter().map(|s| s.to_string()).collect();
+            println!("🪜 Stages: {}", stages.join(", "));
+        }
+        println!();
+    }
+
+    let stop_token = install_stop_handler(human);
+    let batch_size = batch_size.unwrap_or(default_batch_size(method));
+    let hasher = create_hasher(method, cuda_threads, cuda_shared_mem, human);
+
+    let result = scheduler::run_schedule(&entries, stages, &hasher, batch_size, &stop_token, |entry, step_target| {
+        if human {
+            println!("\n▶️  {} → level {}\n", entry.path.display(), step_target);
+        }
+        make_observer(output, step_target)
+    });
+
+    let reports = match result {
+        Ok(reports) => reports,
+        Err(e) => {
+            eprintln!("\n❌ Error during improvement: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if human {
+        if stop_token.is_cancelled() {
+            println!("\n⚠️  Stopped early. Run the same command again to continue from the checkpoints.");
+        }
+        print_identity_table(&reports);
+    } else {
+        for report in &reports {
+            output::emit(&Record::IdentitySummary {
+                file: report.path.display().to_string(),
+                target_level: report.target,
+                start_level: report.start_level,
+                reached_level: report.reached_level,
+                best_counter: report.best_counter,
+                hashes_checked: report.hashes_checked,
+                elapsed_secs: report.elapsed_secs,
+            });
+        }
+    }
+}
+
+/// Print the per-identity result table of `increase-all`
+fn print_identity_table(reports: &[IdentityReport]) {
+    let width = reports.iter()
+        .map(|r| r.path.display().to_string().chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Identity".len());
+
+    println!("\n📋 Results:");
+    println!("   {:<width$}  {:>5}  {:>7}  {:>6}  {:>16}  {:>9}", "Identity", "Start", "Reached", "Target", "Hashes", "Time");
+    for report in reports {
+        let status = if report.reached_level >= report.target { "✅" } else { "⏳" };
+        println!("   {:<width$}  {:>5}  {:>7}  {:>6}  {:>16}  {:>8.1}s  {}",
+                 report.path.display().to_string(),
+                 report.start_level,
+                 report.reached_level,
+                 report.target,
+                 format_number(report.hashes_checked),
+                 report.elapsed_secs,
+                 status);
+    }
+
+    let hashes: u64 = reports.iter().map(|r| r.hashes_checked).sum();
+    let elapsed_secs: f64 = reports.iter().map(|r| r.elapsed_secs).sum();
+    print_statistics(hashes, elapsed_secs);
+}
+
+#[allow(clippy::too_many_arguments)] // Mirrors the CLI arguments
+fn estimate_time(
+    file: Option<String>,
+    current_level: Option<u8>,
+    target_level: u8,
+    hashrate: Option<f64>,
+    method: HasherMethod,
+    calibration_secs: f64,
+    batch_size: Option<usize>,
+    cuda_threads: Option<usize>,
+    cuda_shared_mem: Option<usize>,
+    output: OutputFormat,
+) {
+    let human = output == OutputFormat::Human;
+    let identity = file.as_ref().map(|file_path| match Ts3Identity::from_file(file_path) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("❌ Error loading file '{}': {}", file_path, e);
+            std::process::exit(1);
+        }
+    });
+    let current_level = current_level
+        .or(identity.as_ref().map(Ts3Identity::security_level))
+        .unwrap_or_default();
+
+    // Measure the hashrate unless it was given
+    let (hashes_per_sec, calibrated_with) = match hashrate {
+        Some(hashrate) => (hashrate, None),
+        None => {
+            let hasher = create_hasher(method, cuda_threads, cuda_shared_mem, human);
+            let batch_size = batch_size.unwrap_or(default_batch_size(method));
+            // Any TS3 key has the same length, so a fresh one calibrates just as well
+            let public_key = identity.as_ref().unwrap_or(&Ts3Identity::generate()).public_key_base64();
+            if human {
+                println!("⏱️  Calibrating {} for {:.1}s...", hasher.name(), calibration_secs);
+            }
+            let duration = std::time::Duration::from_secs_f64(calibration_secs.max(0.0));
+            let hashes_per_sec = estimate::calibrate(&hasher, &public_key, batch_size, duration);
+            (hashes_per_sec, Some(hasher.name().to_string()))
+        }
+    };
+
+    let estimate = estimate::Estimate::new(current_level, target_level, hashes_per_sec);
+
+    if !human {
+        output::emit(&Record::Estimate {
+            current_level,
+            target_level,
+            hashes_per_sec,
+            calibrated_with,
+            expected_hashes: estimate.expected_hashes,
+            expected_secs: estimate.secs(estimate.expected_hashes),
+            median_secs: estimate.secs(estimate.median_hashes),
+            p90_secs: estimate.secs(estimate.p90_hashes),
+            p99_secs: estimate.secs(estimate.p99_hashes),
+        });
+        return;
+    }
+
+    println!("\n📈 Estimate: level {} → {}", current_level, target_level);
+    match &calibrated_with {
+        Some(name) => println!("   Hashrate:        {} (measured, {})", format_hashrate(hashes_per_sec), name),
+        None => println!("   Hashrate:        {}", format_hashrate(hashes_per_sec)),
+    }
     if current_level >= target_level {
         println!("\n✅ Identity already at or above target level!");
         return;
     }
 
-    // Setup Ctrl+C handler
+    let hashes = |hashes: f64| if hashes < u64::MAX as f64 {
+        format_number(hashes.round() as u64)
+    } else {
+        format!("{:.3e}", hashes)
+    };
+    println!("   Expected hashes: {} (2^{})", hashes(estimate.expected_hashes), target_level);
+    println!("   Expected time:   {}", format_duration(estimate.secs(estimate.expected_hashes)));
+    println!("   Median (50%):    {}", format_duration(estimate.secs(estimate.median_hashes)));
+    println!("   90% chance:      {}", format_duration(estimate.secs(estimate.p90_hashes)));
+    println!("   99% chance:      {}", format_duration(estimate.secs(estimate.p99_hashes)));
+    println!("\n💡 Each hash reaches level {} with probability 2^-{}, independent of the current level.",
+             target_level, target_level);
+}
+
+/// Cancel the returned token on Ctrl+C instead of exiting, so searches can save their state
+fn install_stop_handler(human: bool) -> StopToken {
+    let stop_token = StopToken::new();
+    let stop_token_handler = stop_token.clone();
     ctrlc::set_handler(move || {
-        println!("\n\n⚠️  Ctrl+C received, stopping gracefully...");
-        std::process::exit(0);
+        if human {
+            println!("\n\n⚠️  Ctrl+C received, stopping gracefully...");
+        } else {
+            eprintln!("Ctrl+C received, stopping gracefully...");
+        }
+        stop_token_handler.cancel();
     }).expect("Error setting Ctrl-C handler");
+    stop_token
+}
 
-    // Determine batch size (use defaults if not specified)
-    let batch_size = batch_size.unwrap_or(match method {
-        HasherMethod::Cpu => 10_000,     // CPU: smaller batches for better responsiveness
+/// Batch size used when --batch-size is not given
+fn default_batch_size(method: HasherMethod) -> usize {
+    match method {
+        HasherMethod::Cpu | HasherMethod::CpuMidstate | HasherMethod::CpuShani => 10_000, // CPU: smaller batches for better responsiveness
+        HasherMethod::CpuSimd => 100_000, // SIMD: larger batches to keep all lanes and cores busy
         HasherMethod::Cuda => 4_000_000, // CUDA: optimal batch size (2.15B h/s, 23% faster than 500k)
-    });
+    }
+}
 
-    // Create improver based on method
-    match method {
-        HasherMethod::Cpu => {
-            println!("⚙️  Method: CPU\n");
-            if let Some(file_path) = file {
-                run_improver_file(&file_path, target_level, CpuHasher, batch_size);
-            } else if let Some(identity_str) = string {
-                run_improver_string(&identity_str, target_level, CpuHasher, batch_size);
-            }
+/// Create the hasher selected on the command line (exits if it is unavailable)
+fn create_hasher(
+    method: HasherMethod,
+    cuda_threads: Option<usize>,
+    cuda_shared_mem: Option<usize>,
+    human: bool,
+) -> Box<dyn SecurityLevelHasher> {
+    let hasher: Box<dyn SecurityLevelHasher> = match method {
+        HasherMethod::Cpu => Box::new(CpuHasher),
+        HasherMethod::CpuMidstate => Box::new(CpuMidstateHasher),
+        HasherMethod::CpuSimd => Box::new(CpuSimdHasher::new()),
+        HasherMethod::CpuShani => Box::new(CpuShaNiHasher::new()),
+        #[cfg(not(feature = "cuda"))]
+        HasherMethod::Cuda => {
+            let _ = (cuda_threads, cuda_shared_mem);
+            eprintln!("❌ CUDA support is not compiled into this binary.");
+            eprintln!("   Rebuild with `cargo build --release --features cuda` (requires the CUDA toolkit),");
+            eprintln!("   or use one of the CPU methods (cpu, cpu-midstate, cpu-simd, cpu-shani).");
+            std::process::exit(1);
         }
+        #[cfg(feature = "cuda")]
         HasherMethod::Cuda => {
-            println!("⚙️  Method: CUDA");
             let threads = cuda_threads.unwrap_or(128);
-            if let Some(mem) = cuda_shared_mem {
-                println!("   Threads per block: {}", threads);
-                println!("   Shared memory: {} bytes\n", mem);
-            } else {
-                println!("   Threads per block: {}", threads);
-                println!("   Shared memory: {} bytes (auto)\n", threads * 128);
+            if human {
+                println!("⚙️  Method: CUDA");
+                if let Some(mem) = cuda_shared_mem {
+                    println!("   Threads per block: {}", threads);
+                    println!("   Shared memory: {} bytes\n", mem);
+                } else {
+                    println!("   Threads per block: {}", threads);
+                    println!("   Shared memory: {} bytes (auto)\n", threads * 128);
+                }
             }
             match CudaHasher::with_params(threads, cuda_shared_mem) {
-                Ok(cuda_hasher) => {
-                    if let Some(file_path) = file {
-                        run_improver_file(&file_path, target_level, cuda_hasher, batch_size);
-                    } else if let Some(identity_str) = string {
-                        run_improver_string(&identity_str, target_level, cuda_hasher, batch_size);
-                    }
-                }
+                Ok(cuda_hasher) => return Box::new(cuda_hasher),
                 Err(e) => {
                     eprintln!("❌ CUDA Error: {}", e);
                     eprintln!("   CUDA support is not yet implemented.");
@@ -157,15 +1303,36 @@ fn increase_level(
                 }
             }
         }
+    };
+
+    // CUDA prints its launch parameters above
+    if human && method != HasherMethod::Cuda {
+        println!("⚙️  Method: {}\n", hasher.name());
     }
+    hasher
 }
 
+/// Observer matching the selected output format
+fn make_observer(output: OutputFormat, target_level: u8) -> Box<dyn ProgressObserver + Send> {
+    match output {
+        OutputFormat::Human => Box::new(TerminalObserver::with_target(target_level)),
+        OutputFormat::Json => Box::new(JsonObserver::with_target(target_level)),
+    }
+}
+
+#[allow(clippy::too_many_arguments)] // Mirrors the CLI arguments
 fn run_improver_file<H: SecurityLevelHasher>(
     file_path: &str,
     target_level: u8,
     hasher: H,
     batch_size: usize,
-) {
+    resume: bool,
+    fresh: bool,
+    shard: Shard,
+    stop_token: StopToken,
+    output: OutputFormat,
+) -> LevelSearchResult {
+    let human = output == OutputFormat::Human;
     let mut improver = match LevelImprover::with_batch_size(file_path, hasher, batch_size) {
         Ok(imp) => imp,
         Err(e) => {
@@ -174,15 +1341,25 @@ fn run_improver_file<H: SecurityLevelHasher>(
         }
     };
 
-    // Flag to signal when we've reached the target level
-    let reached_target = Arc::new(AtomicBool::new(false));
-    let reached_target_clone = reached_target.clone();
+    improver.set_shard(shard);
+    if resume && let Err(e) = improver.resume_from_checkpoint() {
+        eprintln!("❌ Error resuming from checkpoint: {}", e);
+        std::process::exit(1);
+    }
+    if !resume && !fresh && let Err(e) = improver.ensure_no_checkpoint() {
+        eprintln!("❌ {}", e);
+        eprintln!("   Pass --resume to continue it or --fresh to start over (the checkpoint gets overwritten)");
+        std::process::exit(1);
+    }
+    improver.set_stop_token(stop_token);
+    improver.set_observer(make_observer(output, target_level));
 
     let result = improver.improve(|result| {
         if result.level >= target_level {
-            println!("\n✅ TARGET LEVEL {} REACHED!", target_level);
-            println!("   Final counter: {}", result.counter);
-            reached_target_clone.store(true, Ordering::SeqCst);
+            if human {
+                println!("\n✅ TARGET LEVEL {} REACHED!", target_level);
+                println!("   Final counter: {}", result.counter);
+            }
             // Stop searching when target level is reached
             false
         } else {
@@ -191,23 +1368,39 @@ fn run_improver_file<H: SecurityLevelHasher>(
         }
     });
 
-    match result {
-        Ok(_) => {
-            if reached_target.load(Ordering::SeqCst) {
-                println!("\n🎉 Successfully reached target security level {}!", target_level);
-            } else {
-                println!("\n⚠️  Stopped before reaching target level {}.", target_level);
-            }
-        }
+    let outcome = match result {
+        Ok(outcome) => outcome,
         Err(e) => {
             eprintln!("\n❌ Error during improvement: {}", e);
             std::process::exit(1);
         }
+    };
+
+    let checkpoint = improver.checkpoint();
+    let best = LevelSearchResult { counter: checkpoint.best_counter, level: checkpoint.best_level };
+
+    // The JSON observer already emitted the finished and statistics records
+    if !human {
+        return best;
+    }
+
+    match outcome {
+        SearchOutcome::TargetReached(_) => {
+            println!("\n🎉 Successfully reached target security level {}!", target_level);
+        }
+        SearchOutcome::Cancelled => {
+            println!("\n⚠️  Stopped before reaching target level {}.", target_level);
+            println!("   Run again with --resume to continue from {}", improver.checkpoint_path().display());
+        }
+        SearchOutcome::Exhausted => {
+            println!("\n⚠️  Counter space exhausted before reaching target level {}.", target_level);
+        }
     }
 
     // Display final statistics
     let stats = improver.get_statistics();
     print_statistics(stats.hashes_checked, stats.elapsed_secs);
+    best
 }
 
 fn run_improver_string<H: SecurityLevelHasher>(
@@ -215,8 +1408,11 @@ fn run_improver_string<H: SecurityLevelHasher>(
     target_level: u8,
     hasher: H,
     batch_size: usize,
-) {
-    use std::time::{Duration, Instant};
+    shard: Shard,
+    stop_token: StopToken,
+    output: OutputFormat,
+) -> LevelSearchResult {
+    let human = output == OutputFormat::Human;
 
     // Parse the identity
     let identity = match Ts3Identity::parse_identity(identity_str) {
@@ -227,73 +1423,39 @@ fn run_improver_string<H: SecurityLevelHasher>(
         }
     };
 
-    let omega = identity.public_key_base64();
-    let mut current_counter = identity.counter + 1;
-    let mut best_level = identity.security_level();
-    let mut best_counter = identity.counter;
-    let mut hashes_checked: u64 = 0;
-    let start_time = Instant::now();
-    let mut last_print = Instant::now();
+    let mut search = OmegaSearch::with_batch_size(&identity.public_key_base64(), identity.counter, hasher, batch_size);
+    search.set_shard(shard);
+    search.set_stop_token(stop_token);
+    search.set_observer(make_observer(output, target_level));
 
-    println!("\nStarting level improvement search (batch size: {})...", batch_size);
-    println!("Press Ctrl+C to stop\n");
+    let outcome = search.search(|result| result.level < target_level);
 
-    let reached_target = Arc::new(AtomicBool::new(false));
+    // The JSON observer already emitted the finished record with the best counter
+    let best = search.best();
+    if !human {
+        return best;
+    }
 
-    loop {
-        if reached_target.load(Ordering::SeqCst) {
-            break;
+    let mut best_identity = identity;
+    best_identity.counter = best.counter;
+    match outcome {
+        SearchOutcome::TargetReached(_) => {
+            println!("\n🎉 Successfully reached target security level {}!", target_level);
+            println!("\n📋 Final identity string:");
         }
-
-        // Prepare batch of counters to check
-        let counters: Vec<u64> = (current_counter..current_counter + batch_size as u64).collect();
-
-        // Process entire batch at once (parallel on CPU, batched on GPU)
-        let levels = hasher.calculate_levels_batch(&omega, &counters);
-
-        hashes_checked += counters.len() as u64;
-
-        // Check for improved levels in batch results
-        for (i, &level) in levels.iter().enumerate() {
-            let counter = counters[i];
-
-            if level > best_level {
-                best_level = level;
-                best_counter = counter;
-
-                println!("\n🎉 NEW LEVEL FOUND!");
-                println!("   Counter: {}", best_counter);
-                println!("   Level: {}", best_level);
-                println!("   Hashes checked: {}", format_number(hashes_checked));
-                println!("   Updated identity string: {}V{}", best_counter, &identity_str[identity_str.find('V').unwrap() + 1..]);
-
-                if best_level >= target_level {
-                    println!("\n✅ TARGET LEVEL {} REACHED!", target_level);
-                    println!("   Final counter: {}", best_counter);
-                    reached_target.store(true, Ordering::SeqCst);
-                    break;
-                }
-            }
+        SearchOutcome::Cancelled => {
+            println!("\n⚠️  Stopped before reaching target level {}.", target_level);
+            println!("\n📋 Best identity string so far (level {}):", best.level);
         }
-
-        current_counter += batch_size as u64;
-
-        // Print progress every second
-        if last_print.elapsed() >= Duration::from_secs(1) {
-            helpers::print_progress(best_level, current_counter, hashes_checked, start_time);
-            last_print = Instant::now();
+        SearchOutcome::Exhausted => {
+            println!("\n⚠️  Counter space exhausted before reaching target level {}.", target_level);
+            println!("\n📋 Best identity string found (level {}):", best.level);
         }
     }
-
-    if reached_target.load(Ordering::SeqCst) {
-        println!("\n🎉 Successfully reached target security level {}!", target_level);
-        println!("\n📋 Final identity string:");
-        println!("   {}V{}", best_counter, &identity_str[identity_str.find('V').unwrap() + 1..]);
-    } else {
-        println!("\n⚠️  Stopped before reaching target level {}.", target_level);
-    }
+    println!("   {}", best_identity.to_identity_string());
 
     // Display final statistics
-    let elapsed_secs = start_time.elapsed().as_secs_f64();
-    print_statistics(hashes_checked, elapsed_secs);
+    let stats = search.get_statistics();
+    print_statistics(stats.hashes_checked, stats.elapsed_secs);
+    best
 }
@@ -0,0 +1,269 @@
+//! Progress reporting for level improvement searches
+//!
+//! `LevelImprover` reports everything it does through a `ProgressObserver`
+//! instead of printing directly, so library users can silence it
+//! (`NoopObserver`), forward it to a GUI or service (`ChannelObserver`), or keep
+//! the familiar terminal output (`TerminalObserver`, the default).
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+use crate::estimate::TargetOdds;
+use crate::helpers;
+use crate::level_improver::SearchOutcome;
+
+/// How often `TerminalObserver` redraws the progress line
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A search is about to start
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchStarted {
+    /// Identity file the search works on (`None` for identity strings)
+    pub source: Option<String>,
+    pub public_key: String,
+    /// Counter and level stored in the identity
+    pub counter: u64,
+    pub security_level: u8,
+    /// First counter this run will check
+    pub next_counter: u64,
+    /// Best result so far (differs from the identity when resuming)
+    pub best_level: u8,
+    pub best_counter: u64,
+    pub hasher: String,
+    pub batch_size: usize,
+    /// Checkpoint the search was resumed from, with the hashes checked by previous runs
+    pub resumed_from: Option<(PathBuf, u64)>,
+}
+
+/// A batch of counters has been checked
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchCompleted {
+    pub best_level: u8,
+    pub best_counter: u64,
+    /// First counter of the next batch
+    pub next_counter: u64,
+    /// Hashes checked and time spent by this run
+    pub hashes_checked: u64,
+    pub elapsed_secs: f64,
+}
+
+impl BatchCompleted {
+    /// Odds of having reached `target_level`, counting `previous_hashes` from earlier runs
+    pub fn target_odds(&self, target_level: u8, previous_hashes: u64) -> TargetOdds {
+        let hashes_per_sec = if self.elapsed_secs > 0.0 {
+            self.hashes_checked as f64 / self.elapsed_secs
+        } else {
+            0.0
+        };
+        TargetOdds::new(target_level, previous_hashes + self.hashes_checked, hashes_per_sec)
+    }
+}
+
+/// A counter with a higher security level was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewLevelFound {
+    pub counter: u64,
+    pub level: u8,
+    pub hashes_checked: u64,
+    /// The improved identity string (`counter` + "V" + obfuscated key),
+    /// `None` for public-key-only searches
+    pub identity: Option<String>,
+    /// Where the improved identity was written, if anywhere
+    pub saved_to: Option<String>,
+    /// Intermediate level files removed when saving
+    pub removed_files: Vec<String>,
+}
+
+/// The search has stopped
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchFinished {
+    pub outcome: SearchOutcome,
+    pub best_level: u8,
+    pub best_counter: u64,
+    pub hashes_checked: u64,
+    pub elapsed_secs: f64,
+}
+
+/// Receives events from a running search
+///
+/// All methods default to doing nothing, so implementations only override what they need.
+pub trait ProgressObserver {
+    fn on_start(&mut self, _event: &SearchStarted) {}
+
+    /// Called after every batch; implementations should throttle their own output
+    fn on_batch_completed(&mut self, _event: &BatchCompleted) {}
+
+    fn on_new_level(&mut self, _event: &NewLevelFound) {}
+
+    fn on_checkpoint_saved(&mut self, _path: &Path) {}
+
+    fn on_finish(&mut self, _event: &SearchFinished) {}
+}
+
+impl<O: ProgressObserver + ?Sized> ProgressObserver for Box<O> {
+    fn on_start(&mut self, event: &SearchStarted) {
+        (**self).on_start(event);
+    }
+
+    fn on_batch_completed(&mut self, event: &BatchCompleted) {
+        (**self).on_batch_completed(event);
+    }
+
+    fn on_new_level(&mut self, event: &NewLevelFound) {
+        (**self).on_new_level(event);
+    }
+
+    fn on_checkpoint_saved(&mut self, path: &Path) {
+        (**self).on_checkpoint_saved(path);
+    }
+
+    fn on_finish(&mut self, event: &SearchFinished) {
+        (**self).on_finish(event);
+    }
+}
+
+/// Observer that ignores all events
+#[derive(Debug, Clone, Copy, Default)]
+#[allow(dead_code)] // Public API type
+pub struct NoopObserver;
+
+impl ProgressObserver for NoopObserver {}
+
+/// Observer printing human-readable progress to stdout
+#[derive(Debug, Default)]
+pub struct TerminalObserver {
+    last_print: Option<Instant>,
+    last_checkpoint: Option<PathBuf>,
+    target_level: Option<u8>,
+    previous_hashes: u64, // Hashes checked by previous runs (when resuming)
+}
+
+impl TerminalObserver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Observer that also shows ETA, chance so far and luck for `target_level` in the progress line
+    pub fn with_target(target_level: u8) -> Self {
+        Self { target_level: Some(target_level), ..Self::default() }
+    }
+}
+
+impl ProgressObserver for TerminalObserver {
+    fn on_start(&mut self, event: &SearchStarted) {
+        if let Some(source) = &event.source {
+            println!("Loaded identity from {}", source);
+            println!("Current counter: {}", event.counter);
+            println!("Current security level: {}", event.security_level);
+        }
+        println!("Using hasher: {} (batch size: {})", event.hasher, event.batch_size);
+
+        if let Some((checkpoint_path, previous_hashes)) = &event.resumed_from {
+            self.previous_hashes = *previous_hashes;
+            println!("Resuming from checkpoint {}", checkpoint_path.display());
+            println!("Next counter: {}", event.next_counter);
+            println!("Best level so far: {} (counter {})", event.best_level, event.best_counter);
+            println!("Hashes checked in previous runs: {}", helpers::format_number(*previous_hashes));
+        }
+
+        println!("\nStarting level improvement search...");
+        println!("Press Ctrl+C to stop\n");
+        self.last_print = Some(Instant::now());
+    }
+
+    fn on_batch_completed(&mut self, event: &BatchCompleted) {
+        if self.last_print.is_some_and(|last| last.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        let odds = self.target_level.map(|target| event.target_odds(target, self.previous_hashes));
+        helpers::print_progress(event.best_level, event.next_counter, event.hashes_checked, event.elapsed_secs, odds.as_ref());
+        self.last_print = Some(Instant::now());
+    }
+
+    fn on_new_level(&mut self, event: &NewLevelFound) {
+        println!("\n🎉 NEW LEVEL FOUND!");
+        println!("   Counter: {}", event.counter);
+        println!("   Level: {}", event.level);
+        println!("   Hashes checked: {}", helpers::format_number(event.hashes_checked));
+
+        for filename in &event.removed_files {
+            println!("   🗑️  Deleted intermediate file: {}", filename);
+        }
+        match &event.saved_to {
+            Some(path) => println!("   ✓ Progress saved to {}", path),
+            None => match &event.identity {
+                Some(identity) => println!("   Updated identity string: {}", identity),
+                None => println!("   Merge into your identity with counter {}", event.counter),
+            },
+        }
+    }
+
+    fn on_checkpoint_saved(&mut self, path: &Path) {
+        self.last_checkpoint = Some(path.to_path_buf());
+    }
+
+    fn on_finish(&mut self, event: &SearchFinished) {
+        if event.outcome == SearchOutcome::Cancelled
+            && let Some(path) = &self.last_checkpoint
+        {
+            println!("\n   ✓ Checkpoint saved to {}", path.display());
+        }
+    }
+}
+
+/// Every event a `ChannelObserver` forwards
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    Started(SearchStarted),
+    BatchCompleted(BatchCompleted),
+    NewLevel(NewLevelFound),
+    CheckpointSaved(PathBuf),
+    Finished(SearchFinished),
+}
+
+/// Observer forwarding every event over an mpsc channel (for GUIs and services)
+///
+/// Events are dropped silently once the receiver is gone.
+#[derive(Debug, Clone)]
+pub struct ChannelObserver {
+    sender: Sender<ProgressEvent>,
+}
+
+impl ChannelObserver {
+    pub fn new(sender: Sender<ProgressEvent>) -> Self {
+        Self { sender }
+    }
+
+    /// Create an observer together with the receiving end of its channel
+    #[allow(dead_code)] // Public API method
+    pub fn channel() -> (Self, Receiver<ProgressEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        (Self::new(sender), receiver)
+    }
+
+    fn send(&self, event: ProgressEvent) {
+        self.sender.send(event).ok();
+    }
+}
+
+impl ProgressObserver for ChannelObserver {
+    fn on_start(&mut self, event: &SearchStarted) {
+        self.send(ProgressEvent::Started(event.clone()));
+    }
+
+    fn on_batch_completed(&mut self, event: &BatchCompleted) {
+        self.send(ProgressEvent::BatchCompleted(event.clone()));
+    }
+
+    fn on_new_level(&mut self, event: &NewLevelFound) {
+        self.send(ProgressEvent::NewLevel(event.clone()));
+    }
+
+    fn on_checkpoint_saved(&mut self, path: &Path) {
+        self.send(ProgressEvent::CheckpointSaved(path.to_path_buf()));
+    }
+
+    fn on_finish(&mut self, event: &SearchFinished) {
+        self.send(ProgressEvent::Finished(event.clone()));
+    }
+}
@@ -0,0 +1,449 @@
+//! Machine-readable output records
+//!
+//! With `--output json` every result is printed as one JSON object per line
+//! (JSON Lines) instead of the decorated human-readable text. Each record has
+//! an `event` field naming its type.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+use clap::ValueEnum;
+use serde::Serialize;
+use crate::distributed::CoordinatorEvent;
+use crate::estimate::TargetOdds;
+use crate::level_improver::SearchOutcome;
+use crate::observer::{BatchCompleted, NewLevelFound, ProgressObserver, SearchFinished, SearchStarted};
+
+/// How often `JsonObserver` emits a progress record
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How results are written to stdout
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Human,
+    /// One JSON record per line
+    Json,
+}
+
+/// A single JSON output record
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Record {
+    /// Decoded identity fields
+    Identity {
+        uid: String,
+        counter: u64,
+        security_level: u8,
+        public_key: String,
+        nickname: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        uid_matches: Option<bool>,
+    },
+    /// A level improvement search is starting
+    Start {
+        public_key: String,
+        counter: u64,
+        security_level: u8,
+        next_counter: u64,
+        hasher: String,
+        batch_size: usize,
+    },
+    /// A counter with a higher security level was found
+    NewLevel {
+        counter: u64,
+        level: u8,
+        hashes_checked: u64,
+        /// Absent for public-key-only searches
+        #[serde(skip_serializing_if = "Option::is_none")]
+        identity: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        saved_to: Option<String>,
+        /// Worker that found it, for distributed searches
+        #[serde(skip_serializing_if = "Option::is_none")]
+        worker: Option<String>,
+    },
+    /// The search position was written to a checkpoint file
+    Checkpoint {
+        path: String,
+    },
+    /// Periodic progress sample
+    Progress {
+        best_level: u8,
+        counter: u64,
+        hashes_checked: u64,
+        hashes_per_sec: f64,
+        elapsed_secs: f64,
+        /// Target odds (see `estimate::TargetOdds`), present when the target level is known
+        #[serde(skip_serializing_if = "Option::is_none")]
+        target_level: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        eta_secs: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        probability: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        luck: Option<f64>,
+    },
+    /// The search stopped
+    Finished {
+        /// `target_reached`, `cancelled` or `exhausted`
+        outcome: &'static str,
+        target_reached: bool,
+        best_level: u8,
+        best_counter: u64,
+    },
+    /// Per-identity result of `increase-all`
+    IdentitySummary {
+        file: String,
+        target_level: u8,
+        start_level: u8,
+        reached_level: u8,
+        best_counter: u64,
+        hashes_checked: u64,
+        elapsed_secs: f64,
+    },
+    /// The improved counter was written into a settings.db copy
+    DatabaseUpdated {
+        database: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        backup: Option<String>,
+        counter: u64,
+        level: u8,
+        /// Identity names of the updated entries (empty if all were already at this level or higher)
+        updated: Vec<String>,
+    },
+    /// A work package was written by `export-work`
+    WorkExported {
+        path: String,
+        uid: String,
+        counter: u64,
+        target_level: u8,
+    },
+    /// A result file was written by `run-work`
+    WorkResultWritten {
+        path: String,
+        counter: u64,
+        level: u8,
+        target_reached: bool,
+    },
+    /// A validated result was checked against the identity by `import-result`
+    ResultImported {
+        file: String,
+        counter: u64,
+        level: u8,
+        /// Whether the identity file was changed (false if it already had this level or better)
+        updated: bool,
+    },
+    /// A shard output file was re-verified by `merge`
+    ShardChecked {
+        file: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        counter: Option<u64>,
+        /// Re-hashed level of `counter`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        level: Option<u8>,
+        /// Level in the file name (`identity-<level>.ini`), if any
+        #[serde(skip_serializing_if = "Option::is_none")]
+        claimed_level: Option<u8>,
+        /// Why the file was skipped (unreadable or another identity)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// `merge` picked the best shard counter
+    ShardsMerged {
+        file: String,
+        source: String,
+        counter: u64,
+        level: u8,
+        /// Whether the identity file was changed (false if it already had this level or better)
+        updated: bool,
+    },
+    /// Security level check of a public key and counter
+    Verification {
+        uid: String,
+        counter: u64,
+        security_level: u8,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        uid_matches: Option<bool>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        min_level: Option<u8>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        meets_level: Option<bool>,
+    },
+    /// A coordinator is listening for workers
+    CoordinatorStarted {
+        listen: String,
+        uid: String,
+        counter: u64,
+        target_level: u8,
+        best_level: u8,
+        /// Whether the state was loaded from an earlier run
+        resumed: bool,
+    },
+    /// A worker connected to the coordinator
+    WorkerJoined {
+        worker: String,
+        hasher: String,
+    },
+    /// A counter range was handed to a worker
+    RangeAssigned {
+        worker: String,
+        start: u64,
+        end: u64,
+    },
+    /// A worker checked its whole range
+    RangeCompleted {
+        worker: String,
+        start: u64,
+        end: u64,
+    },
+    /// The unchecked rest of a range went back into the pool (worker gone, timed out or revoked)
+    RangeReleased {
+        worker: String,
+        start: u64,
+        end: u64,
+    },
+    /// A worker reported a counter whose level did not verify
+    HitRejected {
+        worker: String,
+        counter: u64,
+        claimed: u8,
+        actual: u8,
+    },
+    /// A range failed the spot checks and will be searched again
+    RangeFlagged {
+        worker: String,
+        start: u64,
+        end: u64,
+        reason: String,
+    },
+    /// Periodic coordinator summary
+    CoordinatorStatus {
+        workers: usize,
+        best_level: u8,
+        best_counter: u64,
+        hashes_checked: u64,
+        hashes_per_sec: f64,
+    },
+    /// Expected work and time to reach a target level
+    Estimate {
+        current_level: u8,
+        target_level: u8,
+        hashes_per_sec: f64,
+        /// Name of the calibrated hasher (absent when the hashrate was given)
+        #[serde(skip_serializing_if = "Option::is_none")]
+        calibrated_with: Option<String>,
+        expected_hashes: f64,
+        expected_secs: f64,
+        median_secs: f64,
+        p90_secs: f64,
+        p99_secs: f64,
+    },
+    /// Final statistics of a search
+    Statistics {
+        hashes_checked: u64,
+        elapsed_secs: f64,
+        hashes_per_sec: f64,
+    },
+}
+
+impl Record {
+    /// Build a progress sample, mirroring `helpers::print_progress`
+    pub fn progress(
+        best_level: u8,
+        counter: u64,
+        hashes_checked: u64,
+        elapsed_secs: f64,
+        odds: Option<&TargetOdds>,
+    ) -> Self {
+        let hashes_per_sec = if elapsed_secs > 0.0 {
+            hashes_checked as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+
+        Record::Progress {
+            best_level,
+            counter,
+            hashes_checked,
+            hashes_per_sec,
+            elapsed_secs,
+            target_level: odds.map(|odds| odds.target_level),
+            eta_secs: odds.map(|odds| odds.eta_secs),
+            probability: odds.map(|odds| odds.probability),
+            luck: odds.map(|odds| odds.luck),
+        }
+    }
+
+    /// Build the record for a coordinator event
+    pub fn coordinator_event(event: &CoordinatorEvent) -> Self {
+        match event.clone() {
+            CoordinatorEvent::WorkerJoined { worker, hasher } => Record::WorkerJoined { worker, hasher },
+            CoordinatorEvent::RangeAssigned { worker, range } => {
+                Record::RangeAssigned { worker, start: range.start, end: range.end }
+            }
+            CoordinatorEvent::RangeCompleted { worker, range } => {
+                Record::RangeCompleted { worker, start: range.start, end: range.end }
+            }
+            CoordinatorEvent::RangeReleased { worker, range } => {
+                Record::RangeReleased { worker, start: range.start, end: range.end }
+            }
+            CoordinatorEvent::NewLevel { worker, counter, level, hashes_checked } => Record::NewLevel {
+                counter,
+                level,
+                hashes_checked,
+                identity: None,
+                saved_to: None,
+                worker: Some(worker),
+            },
+            CoordinatorEvent::HitRejected { worker, counter, claimed, actual } => {
+                Record::HitRejected { worker, counter, claimed, actual }
+            }
+            CoordinatorEvent::RangeFlagged { worker, range, reason } => Record::RangeFlagged {
+                worker,
+                start: range.start,
+                end: range.end,
+                reason: reason.to_string(),
+            },
+            CoordinatorEvent::Status { workers, best_level, best_counter, hashes_checked, hashes_per_sec } => {
+                Record::CoordinatorStatus { workers, best_level, best_counter, hashes_checked, hashes_per_sec }
+            }
+        }
+    }
+
+    /// Build a statistics record, deriving the average hashrate
+    pub fn statistics(hashes_checked: u64, elapsed_secs: f64) -> Self {
+        let hashes_per_sec = if elapsed_secs > 0.0 {
+            hashes_checked as f64 / elapsed_secs
+        } else {
+            0.0
+        };
+
+        Record::Statistics {
+            hashes_checked,
+            elapsed_secs,
+            hashes_per_sec,
+        }
+    }
+}
+
+/// Name of a search outcome as used in `finished` records
+pub fn outcome_name(outcome: &SearchOutcome) -> &'static str {
+    match outcome {
+        SearchOutcome::TargetReached(_) => "target_reached",
+        SearchOutcome::Cancelled => "cancelled",
+        SearchOutcome::Exhausted => "exhausted",
+    }
+}
+
+/// Print a record as a single JSON line
+pub fn emit(record: &Record) {
+    match serde_json::to_string(record) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("Failed to serialize output record: {}", e),
+    }
+}
+
+/// Observer emitting search events as JSON records
+#[derive(Debug, Default)]
+pub struct JsonObserver {
+    last_progress: Option<Instant>,
+    target_level: Option<u8>,
+    previous_hashes: u64, // Hashes checked by previous runs (when resuming)
+}
+
+impl JsonObserver {
+    #[allow(dead_code)] // Public API method
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Observer that also reports the odds of reaching `target_level` in progress records
+    pub fn with_target(target_level: u8) -> Self {
+        Self { target_level: Some(target_level), ..Self::default() }
+    }
+}
+
+impl ProgressObserver for JsonObserver {
+    fn on_start(&mut self, event: &SearchStarted) {
+        emit(&Record::Start {
+            public_key: event.public_key.clone(),
+            counter: event.counter,
+            security_level: event.security_level,
+            next_counter: event.next_counter,
+            hasher: event.hasher.clone(),
+            batch_size: event.batch_size,
+        });
+        self.previous_hashes = event.resumed_from.as_ref().map_or(0, |(_, hashes)| *hashes);
+        self.last_progress = Some(Instant::now());
+    }
+
+    fn on_batch_completed(&mut self, event: &BatchCompleted) {
+        if self.last_progress.is_some_and(|last| last.elapsed() < PROGRESS_INTERVAL) {
+            return;
+        }
+        let odds = self.target_level.map(|target| event.target_odds(target, self.previous_hashes));
+        emit(&Record::progress(event.best_level, event.next_counter, event.hashes_checked, event.elapsed_secs, odds.as_ref()));
+        self.last_progress = Some(Instant::now());
+    }
+
+    fn on_new_level(&mut self, event: &NewLevelFound) {
+        emit(&Record::NewLevel {
+            counter: event.counter,
+            level: event.level,
+            hashes_checked: event.hashes_checked,
+            identity: event.identity.clone(),
+            saved_to: event.saved_to.clone(),
+            worker: None,
+        });
+    }
+
+    fn on_checkpoint_saved(&mut self, path: &Path) {
+        emit(&Record::Checkpoint { path: path.display().to_string() });
+    }
+
+    fn on_finish(&mut self, event: &SearchFinished) {
+        emit(&Record::Finished {
+            outcome: outcome_name(&event.outcome),
+            target_reached: matches!(event.outcome, SearchOutcome::TargetReached(_)),
+            best_level: event.best_level,
+            best_counter: event.best_counter,
+        });
+        emit(&Record::statistics(event.hashes_checked, event.elapsed_secs));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_tagged_with_event() {
+        let record = Record::progress(12, 18_446_744_073_709_551_615, 1000, 2.0, None);
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            json,
+            r#"{"event":"progress","best_level":12,"counter":18446744073709551615,"hashes_checked":1000,"hashes_per_sec":500.0,"elapsed_secs":2.0}"#
+        );
+    }
+
+    #[test]
+    fn test_progress_target_odds() {
+        let odds = TargetOdds::new(10, 2048, 1000.0);
+        let json = serde_json::to_value(Record::progress(9, 2063, 2048, 2.0, Some(&odds))).unwrap();
+        assert_eq!(json["target_level"], 10);
+        assert_eq!(json["eta_secs"], 1.024);
+        assert_eq!(json["luck"], 2.0);
+        assert!(json["probability"].as_f64().unwrap() > 0.86);
+    }
+
+    #[test]
+    fn test_statistics_hashrate() {
+        let json = serde_json::to_value(Record::statistics(1000, 0.5)).unwrap();
+        assert_eq!(json["event"], "statistics");
+        assert_eq!(json["hashes_per_sec"], 2000.0);
+
+        let json = serde_json::to_value(Record::statistics(0, 0.0)).unwrap();
+        assert_eq!(json["hashes_per_sec"], 0.0);
+    }
+}
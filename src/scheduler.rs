@@ -0,0 +1,394 @@
+//! Level improvement for many identities with one hasher
+//!
+//! Identities come from a directory of identity.ini files or from a manifest,
+//! an INI file with one section per identity:
+//!
+//! ```ini
+//! [music-bot]
+//! file = bots/music.ini   ; relative to the manifest
+//! target = 30             ; optional, defaults to --target
+//! priority = 10           ; optional, higher runs first (default 0)
+//! ```
+//!
+//! Work is split into stages: with stages 24 and 30, every identity is raised to
+//! 24 (or its own lower target) before any identity is pushed towards 30.
+//! Within a stage, identities run by descending priority, then in listing order.
+//! Each step resumes from the identity's checkpoint, so no counter is checked twice.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use ini::Ini;
+use thiserror::Error;
+use crate::checkpoint::Checkpoint;
+use crate::identity::{IdentityError, Ts3Identity};
+use crate::level_improver::{LevelImprover, SearchOutcome, SecurityLevelHasher, StopToken};
+use crate::observer::ProgressObserver;
+
+#[derive(Debug, Error)]
+pub enum ScheduleError {
+    #[error("Failed to read identities: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Failed to parse manifest: {0}")]
+    IniError(String),
+
+    #[error("Missing 'file' key in manifest section [{0}]")]
+    MissingFile(String),
+
+    #[error("Invalid value for '{0}' in manifest section [{1}]: {2}")]
+    InvalidValue(&'static str, String, String),
+
+    #[error("No target level for {0} (set 'target' in the manifest or pass --target)")]
+    MissingTarget(String),
+
+    #[error("No identity files found in {0}")]
+    NoIdentities(String),
+
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] IdentityError),
+}
+
+/// One identity file and the level it should reach
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledIdentity {
+    pub path: PathBuf,
+    pub target: u8,
+    /// Higher priorities run first within a stage
+    pub priority: i32,
+}
+
+/// Result for one identity after the schedule ran
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityReport {
+    pub path: PathBuf,
+    pub target: u8,
+    /// Level stored in the identity file before this run
+    pub start_level: u8,
+    /// Best level reached so far (including earlier runs recorded in the checkpoint)
+    pub reached_level: u8,
+    pub best_counter: u64,
+    pub hashes_checked: u64,
+    pub elapsed_secs: f64,
+}
+
+/// Collect the identity files of a directory, all with the same target level
+///
+/// Files that are not TS3 identities and the `basename-<level>.ini` files written
+/// by previous searches are skipped.
+pub fn from_directory<P: AsRef<Path>>(dir: P, target: u8) -> Result<Vec<ScheduledIdentity>, ScheduleError> {
+    let dir = dir.as_ref();
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ini"))
+        .collect();
+    paths.sort();
+
+    let entries: Vec<ScheduledIdentity> = paths.iter()
+        .filter(|path| !is_level_file(path))
+        .filter(|path| Ts3Identity::from_file(path).is_ok())
+        .map(|path| ScheduledIdentity { path: path.clone(), target, priority: 0 })
+        .collect();
+
+    if entries.is_empty() {
+        return Err(ScheduleError::NoIdentities(dir.display().to_string()));
+    }
+    Ok(entries)
+}
+
+/// Whether `path` is a `basename-<level>.ini` file saved next to `basename.ini`
+fn is_level_file(path: &Path) -> bool {
+    let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+        return false;
+    };
+    match stem.rsplit_once('-') {
+        Some((base, level)) => level.parse::<u8>().is_ok() && path.with_file_name(format!("{}.ini", base)).exists(),
+        None => false,
+    }
+}
+
+/// Read a manifest; identity paths are relative to the manifest's directory
+pub fn from_manifest<P: AsRef<Path>>(path: P, default_target: Option<u8>) -> Result<Vec<ScheduledIdentity>, ScheduleError> {
+    let path = path.as_ref();
+    let conf = Ini::load_from_str(&strip_comments(&fs::read_to_string(path)?))
+        .map_err(|e| ScheduleError::IniError(e.to_string()))?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut entries = Vec::new();
+    for (section, props) in conf.iter() {
+        let Some(name) = section else {
+            continue;
+        };
+
+        let file = props.get("file").ok_or_else(|| ScheduleError::MissingFile(name.to_string()))?;
+        let target = match props.get("target") {
+            Some(value) => value.trim().parse::<u8>()
+                .map_err(|e| ScheduleError::InvalidValue("target", name.to_string(), e.to_string()))?,
+            None => default_target.ok_or_else(|| ScheduleError::MissingTarget(file.to_string()))?,
+        };
+        let priority = match props.get("priority") {
+            Some(value) => value.trim().parse::<i32>()
+                .map_err(|e| ScheduleError::InvalidValue("priority", name.to_string(), e.to_string()))?,
+            None => 0,
+        };
+
+        entries.push(ScheduledIdentity { path: base_dir.join(file.trim()), target, priority });
+    }
+
+    if entries.is_empty() {
+        return Err(ScheduleError::NoIdentities(path.display().to_string()));
+    }
+    Ok(entries)
+}
+
+/// Remove `;` and `#` comments, including ones after a value
+///
+/// The `ini` crate only knows whole-line comments. An inline comment must follow
+/// whitespace, so `#` and `;` inside file names survive.
+fn strip_comments(manifest: &str) -> String {
+    manifest
+        .lines()
+        .map(|line| {
+            let comment = line.char_indices().find(|&(i, c)| {
+                (c == ';' || c == '#') && line[..i].chars().next_back().is_none_or(char::is_whitespace)
+            });
+            match comment {
+                Some((i, _)) => line[..i].trim_end(),
+                None => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Order the work: a list of (entry index, level to reach in this step)
+///
+/// Each stage caps the targets at its level; a final stage runs everything to its own target.
+pub fn plan(entries: &[ScheduledIdentity], stages: &[u8]) -> Vec<(usize, u8)> {
+    let max_target = entries.iter().map(|e| e.target).max().unwrap_or(0);
+    let mut stage_levels: Vec<u8> = stages.iter().copied().filter(|&s| s < max_target).collect();
+    stage_levels.push(max_target);
+    stage_levels.sort_unstable();
+    stage_levels.dedup();
+
+    // Stable sort keeps the listing order for equal priorities
+    let mut order: Vec<usize> = (0..entries.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(entries[i].priority));
+
+    let mut steps = Vec::new();
+    let mut previous_stage = 0;
+    for stage in stage_levels {
+        for &i in &order {
+            let step_target = entries[i].target.min(stage);
+            // Identities whose target lies below this stage were finished in an earlier one
+            if step_target > previous_stage {
+                steps.push((i, step_target));
+            }
+        }
+        previous_stage = stage;
+    }
+    steps
+}
+
+/// Run all entries through one hasher according to `plan`
+///
+/// `make_observer` is called once per step with the entry and the level that step aims for.
+/// Cancelling `stop_token` ends the whole schedule after the running step has written
+/// its checkpoint; the reports then show how far each identity got.
+pub fn run_schedule<H, O, F>(
+    entries: &[ScheduledIdentity],
+    stages: &[u8],
+    hasher: &H,
+    batch_size: usize,
+    stop_token: &StopToken,
+    mut make_observer: F,
+) -> Result<Vec<IdentityReport>, ScheduleError>
+where
+    H: SecurityLevelHasher + ?Sized,
+    O: ProgressObserver + Send + 'static,
+    F: FnMut(&ScheduledIdentity, u8) -> O,
+{
+    let mut reports = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let identity = Ts3Identity::from_file(&entry.path)?;
+        let start_level = identity.security_level();
+        let mut report = IdentityReport {
+            path: entry.path.clone(),
+            target: entry.target,
+            start_level,
+            reached_level: start_level,
+            best_counter: identity.counter,
+            hashes_checked: 0,
+            elapsed_secs: 0.0,
+        };
+
+        // Progress from earlier runs counts towards the reached level
+        let checkpoint_path = Checkpoint::path_for(&entry.path);
+        if checkpoint_path.exists() {
+            let checkpoint = Checkpoint::load_for_key(&checkpoint_path, &identity.public_key_base64())
+                .map_err(IdentityError::from)?;
+            if checkpoint.best_level > report.reached_level {
+                report.reached_level = checkpoint.best_level;
+                report.best_counter = checkpoint.best_counter;
+            }
+        }
+        reports.push(report);
+    }
+
+    let mut exhausted = vec![false; entries.len()];
+    for (i, step_target) in plan(entries, stages) {
+        if stop_token.is_cancelled() {
+            break;
+        }
+        if exhausted[i] || reports[i].reached_level >= step_target {
+            continue;
+        }
+
+        let entry = &entries[i];
+        let step_start = Instant::now();
+        let mut improver = LevelImprover::with_batch_size(&entry.path, hasher, batch_size)?;
+        if improver.checkpoint_path().exists() {
+            improver.resume_from_checkpoint()?;
+        }
+        improver.set_stop_token(stop_token.clone());
+        improver.set_observer(make_observer(entry, step_target));
+
+        let outcome = improver.improve(|result| result.level < step_target)?;
+
+        let checkpoint = improver.checkpoint();
+        let report = &mut reports[i];
+        report.reached_level = checkpoint.best_level;
+        report.best_counter = checkpoint.best_counter;
+        report.hashes_checked += improver.get_statistics().hashes_checked;
+        report.elapsed_secs += step_start.elapsed().as_secs_f64();
+
+        match outcome {
+            SearchOutcome::Cancelled => break,
+            SearchOutcome::Exhausted => exhausted[i] = true,
+            SearchOutcome::TargetReached(_) => {}
+        }
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::temp_test_dir;
+    use crate::hashers::CpuHasher;
+    use crate::observer::NoopObserver;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        temp_test_dir(&format!("schedule-{}", name))
+    }
+
+    fn entry(name: &str, target: u8, priority: i32) -> ScheduledIdentity {
+        ScheduledIdentity { path: PathBuf::from(name), target, priority }
+    }
+
+    #[test]
+    fn test_plan_stages_before_final_targets() {
+        let entries = [entry("a", 30, 0), entry("b", 20, 0), entry("c", 26, 0)];
+        let steps = plan(&entries, &[24]);
+        assert_eq!(steps, vec![(0, 24), (1, 20), (2, 24), (0, 30), (2, 26)]);
+    }
+
+    #[test]
+    fn test_plan_priorities() {
+        let entries = [entry("a", 24, 0), entry("b", 24, 5), entry("c", 24, 0)];
+        assert_eq!(plan(&entries, &[]), vec![(1, 24), (0, 24), (2, 24)]);
+    }
+
+    #[test]
+    fn test_from_manifest() {
+        let dir = temp_dir("manifest");
+        let manifest = dir.join("bots.ini");
+        fs::write(&manifest, "[music]\nfile = bots/music.ini\ntarget = 30\npriority = 2\n\n[admin]\nfile = admin.ini\n").unwrap();
+
+        let entries = from_manifest(&manifest, Some(24)).unwrap();
+        let missing_target = from_manifest(&manifest, None);
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(entries, vec![
+            ScheduledIdentity { path: dir.join("bots/music.ini"), target: 30, priority: 2 },
+            ScheduledIdentity { path: dir.join("admin.ini"), target: 24, priority: 0 },
+        ]);
+        assert!(matches!(missing_target, Err(ScheduleError::MissingTarget(_))));
+    }
+
+    #[test]
+    fn test_from_manifest_with_comments() {
+        let dir = temp_dir("manifest-comments");
+        let manifest = dir.join("bots.ini");
+
+        // The example from the README, verbatim
+        fs::write(&manifest, "\
+; bots.ini — paths are relative to the manifest
+[music-bot]
+file = bots/music.ini
+target = 30
+priority = 10   ; higher runs first within a stage (default 0)
+
+[admin-bot]
+file = bots/admin.ini   ; uses --target
+").unwrap();
+        let readme = from_manifest(&manifest, Some(24));
+
+        // The example from the module documentation, plus a '#' inside a file name
+        fs::write(&manifest, "\
+[music-bot]
+file = bots/music.ini   ; relative to the manifest
+target = 30             ; optional, defaults to --target
+priority = 10           ; optional, higher runs first (default 0)
+
+[radio-bot]
+file = bots/radio#2.ini # the second radio
+").unwrap();
+        let module_doc = from_manifest(&manifest, Some(24));
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(readme.unwrap(), vec![
+            ScheduledIdentity { path: dir.join("bots/music.ini"), target: 30, priority: 10 },
+            ScheduledIdentity { path: dir.join("bots/admin.ini"), target: 24, priority: 0 },
+        ]);
+        assert_eq!(module_doc.unwrap(), vec![
+            ScheduledIdentity { path: dir.join("bots/music.ini"), target: 30, priority: 10 },
+            ScheduledIdentity { path: dir.join("bots/radio#2.ini"), target: 24, priority: 0 },
+        ]);
+    }
+
+    #[test]
+    fn test_from_directory_skips_level_files() {
+        let dir = temp_dir("directory");
+        fs::copy("inis/level8.ini", dir.join("a.ini")).unwrap();
+        fs::copy("inis/level8.ini", dir.join("a-12.ini")).unwrap();
+        fs::copy("inis/level22.ini", dir.join("b.ini")).unwrap();
+        fs::write(dir.join("notes.ini"), "[General]\nkey = value\n").unwrap();
+
+        let entries = from_directory(&dir, 20).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        let names: Vec<_> = entries.iter().map(|e| e.path.file_name().unwrap().to_owned()).collect();
+        assert_eq!(names, ["a.ini", "b.ini"]);
+    }
+
+    #[test]
+    fn test_run_schedule() {
+        let dir = temp_dir("run");
+        fs::copy("inis/level8.ini", dir.join("a.ini")).unwrap();
+        fs::copy("inis/level8.ini", dir.join("b.ini")).unwrap();
+        let entries = [
+            ScheduledIdentity { path: dir.join("a.ini"), target: 12, priority: 0 },
+            ScheduledIdentity { path: dir.join("b.ini"), target: 9, priority: 0 },
+        ];
+
+        let reports = run_schedule(&entries, &[9], &CpuHasher, 100, &StopToken::new(), |_, _| NoopObserver).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        let levels: Vec<_> = reports.iter().map(|r| (r.start_level, r.reached_level, r.best_counter)).collect();
+        assert_eq!(levels, [(8, 12, 672), (8, 9, 201)]);
+        // a.ini ran twice (to 9, then to 12) without rechecking counters 15..=201
+        assert_eq!(reports[0].hashes_checked, 200 + 500);
+    }
+}
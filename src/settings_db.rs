@@ -0,0 +1,433 @@
+//! Import identities from the TeamSpeak 3 client's settings.db
+//!
+//! Besides identity.ini exports, the client keeps its identity manager in the
+//! SQLite database `settings.db`. The `Identities` table stores `(timestamp, key, value)`
+//! rows, and its `Identities` row holds a QSettings-style INI blob with one numbered
+//! entry per identity:
+//!
+//! ```ini
+//! [Identities]
+//! 1\id=Default
+//! 1\identity="14V..."
+//! 1\nickname=TeamSpeakUser
+//! size=1
+//! ```
+//!
+//! Work on a copy while the client is running. `update_counter` writes an improved
+//! counter back into such a copy, after taking a timestamped backup.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use ini::Ini;
+use rusqlite::{Connection, OpenFlags};
+use rusqlite::types::{Value, ValueRef};
+use thiserror::Error;
+use crate::identity::{IdentityError, Ts3Identity};
+
+#[derive(Debug, Error)]
+pub enum SettingsDbError {
+    #[error("Failed to read settings database: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[error("Failed to parse identity list: {0}")]
+    IniError(String),
+
+    #[error("Invalid identity #{0}: {1}")]
+    IdentityError(usize, IdentityError),
+
+    #[error("No identities stored in the settings database")]
+    NoIdentities,
+
+    #[error("No identity with nickname or name '{0}'")]
+    NotFound(String),
+
+    #[error("'{0}' matches several identities ({1}); use the identity name instead")]
+    Ambiguous(String, String),
+
+    #[error("No stored identity has the public key {0}")]
+    PublicKeyNotFound(String),
+
+    #[error("Failed to back up settings database: {0}")]
+    BackupError(String),
+
+    #[error("Rewritten identity #{0} does not match the expected key and counter")]
+    VerificationFailed(usize),
+}
+
+/// An identity from the client's identity manager
+#[derive(Debug, Clone)]
+pub struct StoredIdentity {
+    /// Position in the identity manager (the `N\` key prefix, starting at 1)
+    pub index: usize,
+    /// The parsed identity, with `name` and `nickname` from the stored entry
+    pub identity: Ts3Identity,
+}
+
+/// Result of writing an improved counter back into a settings.db copy
+#[derive(Debug, Clone)]
+pub struct CounterUpdate {
+    /// Copy of the database taken before writing (`None` if nothing needed updating)
+    pub backup_path: Option<PathBuf>,
+    /// Updated entries with their previous counter
+    pub updated: Vec<(StoredIdentity, u64)>,
+}
+
+/// A row of the `Identities` table
+struct IdentitiesRow {
+    rowid: i64,
+    value: String,
+    is_blob: bool, // Older clients store the blob as TEXT, newer ones as BLOB
+}
+
+fn read_rows(conn: &Connection) -> Result<Vec<IdentitiesRow>, SettingsDbError> {
+    let mut statement = conn.prepare("SELECT rowid, value FROM Identities WHERE key = 'Identities'")?;
+    let rows = statement.query_map([], |row| {
+        let value = row.get_ref(1)?;
+        Ok(IdentitiesRow {
+            rowid: row.get(0)?,
+            value: value.as_bytes().map(|bytes| String::from_utf8_lossy(bytes).into_owned()).unwrap_or_default(),
+            is_blob: matches!(value, ValueRef::Blob(_)),
+        })
+    })?;
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Numbered entries of an identity list blob: index -> (key -> value),
+/// e.g. 1 -> {"identity": "14V...", "nickname": "..."}
+fn parse_entries(value: &str) -> Result<BTreeMap<usize, BTreeMap<String, String>>, SettingsDbError> {
+    let conf = Ini::load_from_str_noescape(value)
+        .map_err(|e| SettingsDbError::IniError(e.to_string()))?;
+
+    let mut entries: BTreeMap<usize, BTreeMap<String, String>> = BTreeMap::new();
+    for (_, properties) in conf.iter() {
+        for (key, value) in properties.iter() {
+            if let Some((index, field)) = key.split_once('\\')
+                && let Ok(index) = index.parse::<usize>()
+            {
+                entries.entry(index).or_default().insert(field.to_string(), value.to_string());
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// Parse one numbered entry (`None` if it has no `identity` key)
+fn entry_identity(index: usize, fields: &BTreeMap<String, String>) -> Option<Result<StoredIdentity, SettingsDbError>> {
+    let identity_str = fields.get("identity")?;
+    let field = |key: &str| fields.get(key).cloned().unwrap_or_default();
+    Some(Ts3Identity::parse_identity(identity_str)
+        .map(|mut identity| {
+            identity.name = field("id");
+            identity.nickname = field("nickname");
+            identity.phonetic_nickname = field("phonetic_nickname");
+            StoredIdentity { index, identity }
+        })
+        .map_err(|e| SettingsDbError::IdentityError(index, e)))
+}
+
+/// Read every identity stored in a settings.db file, in identity manager order
+pub fn read_identities<P: AsRef<Path>>(path: P) -> Result<Vec<StoredIdentity>, SettingsDbError> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let mut entries = BTreeMap::new();
+    for row in read_rows(&conn)? {
+        entries.extend(parse_entries(&row.value)?);
+    }
+
+    let identities = entries.iter()
+        .filter_map(|(&index, fields)| entry_identity(index, fields))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if identities.is_empty() {
+        return Err(SettingsDbError::NoIdentities);
+    }
+    Ok(identities)
+}
+
+/// Find the identity whose nickname or identity name is `name`
+///
+/// Fails if no identity or more than one identity matches.
+pub fn find_identity<P: AsRef<Path>>(path: P, name: &str) -> Result<StoredIdentity, SettingsDbError> {
+    let mut matches: Vec<StoredIdentity> = read_identities(path)?
+        .into_iter()
+        .filter(|stored| stored.identity.nickname == name || stored.identity.name == name)
+        .collect();
+
+    match matches.len() {
+        0 => Err(SettingsDbError::NotFound(name.to_string())),
+        1 => Ok(matches.remove(0)),
+        _ => {
+            let names: Vec<&str> = matches.iter().map(|stored| stored.identity.name.as_str()).collect();
+            Err(SettingsDbError::Ambiguous(name.to_string(), names.join(", ")))
+        }
+    }
+}
+
+/// Write `identity`'s counter into every stored entry with the same public key
+///
+/// Entries are matched by public key, never by name, and entries that already have
+/// the same or a higher level are left alone. Before anything is written the whole
+/// database is copied to `<path>.<unix time>.bak`; no backup is made if nothing changes.
+/// The stored keys are checked again in the rewritten data before it is committed.
+pub fn update_counter<P: AsRef<Path>>(path: P, identity: &Ts3Identity) -> Result<CounterUpdate, SettingsDbError> {
+    let path = path.as_ref();
+    let public_key = identity.public_key_base64();
+    let new_level = identity.security_level();
+    let mut conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+
+    // (row, new value, updated entries) for every row that changes
+    let mut changes = Vec::new();
+    let mut key_found = false;
+    for row in read_rows(&conn)? {
+        let mut value = row.value.clone();
+        let mut updated = Vec::new();
+
+        for (index, fields) in parse_entries(&row.value)? {
+            let Some(mut stored) = entry_identity(index, &fields).transpose()? else { continue };
+            if stored.identity.public_key_base64() != public_key {
+                continue;
+            }
+            key_found = true;
+            if stored.identity.security_level() >= new_level {
+                continue;
+            }
+
+            value = replace_counter(&value, index, identity.counter);
+            let previous_counter = stored.identity.counter;
+            stored.identity.counter = identity.counter;
+            updated.push((stored, previous_counter));
+        }
+
+        if updated.is_empty() {
+            continue;
+        }
+
+        // Re-read the rewritten blob: each updated entry must still hold this key, with the new counter
+        let rewritten = parse_entries(&value)?;
+        for (stored, _) in &updated {
+            let verified = rewritten.get(&stored.index)
+                .and_then(|fields| entry_identity(stored.index, fields))
+                .and_then(Result::ok)
+                .is_some_and(|entry| entry.identity.public_key_base64() == public_key
+                    && entry.identity.counter == identity.counter);
+            if !verified {
+                return Err(SettingsDbError::VerificationFailed(stored.index));
+            }
+        }
+        changes.push((row, value, updated));
+    }
+
+    if !key_found {
+        return Err(SettingsDbError::PublicKeyNotFound(public_key));
+    }
+
+    if changes.is_empty() {
+        return Ok(CounterUpdate { backup_path: None, updated: Vec::new() });
+    }
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let backup_path = (0..)
+        .map(|attempt| backup_path_for(path, timestamp, attempt))
+        .find(|candidate| !candidate.exists())
+        .expect("unbounded range always yields a free name");
+
+    // VACUUM INTO produces a consistent copy even while the database is in use
+    conn.execute("VACUUM INTO ?1", [backup_path.to_string_lossy()])
+        .map_err(|e| SettingsDbError::BackupError(e.to_string()))?;
+
+    let transaction = conn.transaction()?;
+    let mut all_updated = Vec::new();
+    for (row, value, updated) in changes {
+        let value = if row.is_blob { Value::Blob(value.into_bytes()) } else { Value::Text(value) };
+        transaction.execute(
+            "UPDATE Identities SET value = ?1, timestamp = ?2 WHERE rowid = ?3",
+            rusqlite::params![value, timestamp as i64, row.rowid],
+        )?;
+        all_updated.extend(updated);
+    }
+    transaction.commit()?;
+
+    Ok(CounterUpdate { backup_path: Some(backup_path), updated: all_updated })
+}
+
+/// `<path>.<timestamp>.bak`, or `<path>.<timestamp>-<attempt>.bak` if that is taken
+fn backup_path_for(path: &Path, timestamp: u64, attempt: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    match attempt {
+        0 => name.push(format!(".{}.bak", timestamp)),
+        _ => name.push(format!(".{}-{}.bak", timestamp, attempt)),
+    }
+    PathBuf::from(name)
+}
+
+/// Replace the counter of entry `index` in an identity list blob, keeping everything else as stored
+fn replace_counter(value: &str, index: usize, counter: u64) -> String {
+    let prefix = format!("{}\\identity=", index);
+    value.split_inclusive('\n')
+        .map(|line| {
+            let Some(stored) = line.strip_prefix(&prefix) else {
+                return line.to_string();
+            };
+            // `"14V..."` -> `"<counter>V..."`, keeping quotes, key text and line ending
+            let digits_start = stored.find(|c: char| c.is_ascii_digit()).unwrap_or(0);
+            let digits_end = stored[digits_start..].find(|c: char| !c.is_ascii_digit())
+                .map_or(stored.len(), |end| digits_start + end);
+            format!("{}{}{}{}", prefix, &stored[..digits_start], counter, &stored[digits_end..])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::temp_test_dir;
+    use std::fs;
+
+    const FIXTURE: &str = "inis/settings.db";
+
+    #[test]
+    fn test_read_identities() {
+        let identities = read_identities(FIXTURE).unwrap();
+
+        let summary: Vec<(usize, &str, &str, u8)> = identities.iter()
+            .map(|stored| (
+                stored.index,
+                stored.identity.name.as_str(),
+                stored.identity.nickname.as_str(),
+                stored.identity.security_level(),
+            ))
+            .collect();
+        assert_eq!(summary, vec![
+            (1, "Default", "TeamSpeakUser", 22),
+            (2, "Bots", "MusicBot", 8),
+            (3, "Backup", "TeamSpeakUser", 12),
+        ]);
+        assert_eq!(identities[1].identity.uid(), "l26GuCq+OpJFe58bQzysddzPoYg=");
+    }
+
+    #[test]
+    fn test_find_identity() {
+        let stored = find_identity(FIXTURE, "MusicBot").unwrap();
+        assert_eq!((stored.index, stored.identity.counter), (2, 14));
+
+        // Identity names disambiguate shared nicknames
+        let stored = find_identity(FIXTURE, "Backup").unwrap();
+        assert_eq!((stored.index, stored.identity.counter), (3, 672));
+
+        assert!(matches!(find_identity(FIXTURE, "TeamSpeakUser"), Err(SettingsDbError::Ambiguous(_, names)) if names == "Default, Backup"));
+        assert!(matches!(find_identity(FIXTURE, "Nobody"), Err(SettingsDbError::NotFound(_))));
+    }
+
+    /// Copy the fixture database into a fresh temporary directory
+    fn temp_database(name: &str) -> PathBuf {
+        let dir = temp_test_dir(&format!("settings-db-{}", name));
+        let path = dir.join("settings.db");
+        fs::copy(FIXTURE, &path).unwrap();
+        path
+    }
+
+    fn counters(path: &Path) -> Vec<u64> {
+        read_identities(path).unwrap().iter().map(|stored| stored.identity.counter).collect()
+    }
+
+    #[test]
+    fn test_update_counter() {
+        let path = temp_database("update");
+        let mut identity = find_identity(&path, "MusicBot").unwrap().identity;
+        identity.counter = 8059; // level 18
+
+        let update = update_counter(&path, &identity).unwrap();
+        let backup_path = update.backup_path.clone().unwrap();
+        let (updated, backup_counters) = (counters(&path), counters(&backup_path));
+        let stored = read_identities(&path).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        // Both entries with this key are raised, the other identity is untouched
+        let changed: Vec<(usize, u64)> = update.updated.iter()
+            .map(|(stored, previous)| (stored.index, *previous))
+            .collect();
+        assert_eq!(changed, vec![(2, 14), (3, 672)]);
+        assert_eq!(updated, vec![1118470, 8059, 8059]);
+        assert_eq!(backup_counters, vec![1118470, 14, 672]);
+        assert_eq!((stored[1].identity.nickname.as_str(), stored[1].identity.security_level()), ("MusicBot", 18));
+    }
+
+    #[test]
+    fn test_update_counter_keeps_higher_levels() {
+        let path = temp_database("keep");
+        let mut identity = find_identity(&path, "MusicBot").unwrap().identity;
+
+        // Counter 14 is what is stored already: nothing to do, no backup
+        let unchanged = update_counter(&path, &identity).unwrap();
+
+        // Level 9 only beats the level 8 entry, not the level 12 backup entry
+        identity.counter = 201;
+        let update = update_counter(&path, &identity).unwrap();
+        let updated = counters(&path);
+
+        // A second export right away gets its own backup file
+        identity.counter = 672;
+        let second_update = update_counter(&path, &identity).unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert!(unchanged.updated.is_empty() && unchanged.backup_path.is_none());
+        assert_eq!(update.updated.len(), 1);
+        assert_eq!(updated, vec![1118470, 201, 672]);
+        assert_eq!(second_update.updated.len(), 1);
+        assert_ne!(update.backup_path, second_update.backup_path);
+    }
+
+    #[test]
+    fn test_update_counter_unknown_key() {
+        let path = temp_database("unknown");
+        let identity = Ts3Identity::generate();
+
+        let result = update_counter(&path, &identity);
+        let files = fs::read_dir(path.parent().unwrap()).unwrap().count();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert!(matches!(result, Err(SettingsDbError::PublicKeyNotFound(_))));
+        assert_eq!(files, 1, "no backup is written when nothing matches");
+    }
+
+    #[test]
+    fn test_other_keys_ignored() {
+        let path = temp_database("other-keys");
+        let stored = find_identity(&path, "MusicBot").unwrap().identity;
+        // Same key as MusicBot, but stored under a key the client does not read
+        let other = format!("[Identities]\r\n1\\id=Other\r\n1\\identity=\"{}\"\r\n", stored.to_identity_string());
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute("INSERT INTO Identities (timestamp, key, value) VALUES (0, 'Identities_old', ?1)", [&other]).unwrap();
+        }
+
+        let identities = read_identities(&path).unwrap();
+        let mut identity = stored;
+        identity.counter = 8059;
+        let update = update_counter(&path, &identity).unwrap();
+        let other_value: String = Connection::open(&path).unwrap()
+            .query_row("SELECT value FROM Identities WHERE key = 'Identities_old'", [], |row| row.get(0))
+            .unwrap();
+        fs::remove_dir_all(path.parent().unwrap()).ok();
+
+        assert_eq!(identities.len(), 3);
+        assert_eq!(update.updated.len(), 2);
+        assert_eq!(other_value, other);
+    }
+
+    #[test]
+    fn test_replace_counter() {
+        let value = "[Identities]\r\n2\\id=Bots\r\n2\\identity=\"14VabcV1\"\r\n12\\identity=\"14Vdef\"\r\n";
+        assert_eq!(
+            replace_counter(value, 2, 8059),
+            "[Identities]\r\n2\\id=Bots\r\n2\\identity=\"8059VabcV1\"\r\n12\\identity=\"14Vdef\"\r\n"
+        );
+    }
+
+    #[test]
+    fn test_missing_database() {
+        let result = read_identities("inis/does-not-exist.db");
+        assert!(matches!(result, Err(SettingsDbError::SqliteError(_))));
+    }
+}
@@ -0,0 +1,201 @@
+//! Deterministic counter-space sharding for machines without a coordinator
+//!
+//! The counter space is cut into fixed blocks of [`SHARD_BLOCK_SIZE`] counters,
+//! counted from zero, and shard `i` of `n` owns every block whose index is `i - 1`
+//! modulo `n`. Because the blocks are absolute rather than relative to a starting
+//! counter, shards never overlap even if the machines start from different counters
+//! (for example after one of them already saved an improved identity), and every
+//! shard keeps its counters as short as the others.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use crate::level_improver::counter_batch;
+
+/// Counters per block; every machine of a sharded search must use the same value
+pub const SHARD_BLOCK_SIZE: u64 = 1 << 24;
+
+/// One part of an `n`-way split of the counter space (`1/1` is the whole space)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Shard {
+    index: u64, // Zero-based
+    count: u64,
+}
+
+impl Default for Shard {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl Shard {
+    /// The whole counter space
+    pub const ALL: Shard = Shard { index: 0, count: 1 };
+
+    /// Shard `number` of `count`, both counted from 1 as on the command line
+    pub fn new(number: u64, count: u64) -> Option<Self> {
+        (count >= 1 && (1..=count).contains(&number)).then(|| Self { index: number - 1, count })
+    }
+
+    /// Shard number, counted from 1
+    pub fn number(&self) -> u64 {
+        self.index + 1
+    }
+
+    /// Total number of shards
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Whether this shard covers the whole counter space
+    pub fn is_all(&self) -> bool {
+        self.count == 1
+    }
+
+    /// Tag for the file names of this shard's checkpoint and level files (`.shard2of4`),
+    /// empty for the whole space
+    pub fn file_tag(&self) -> String {
+        if self.is_all() {
+            String::new()
+        } else {
+            format!(".shard{}of{}", self.number(), self.count)
+        }
+    }
+
+    /// Whether `counter` belongs to this shard
+    #[allow(dead_code)] // Public API method
+    pub fn contains(&self, counter: u64) -> bool {
+        (counter / SHARD_BLOCK_SIZE) % self.count == self.index
+    }
+
+    /// First counter of this shard at or after `counter`, `None` if there is none left
+    pub fn first_at_or_after(&self, counter: u64) -> Option<u64> {
+        let block = counter / SHARD_BLOCK_SIZE;
+        let skip = (self.index + self.count - block % self.count) % self.count;
+        if skip == 0 {
+            return Some(counter);
+        }
+        block.checked_add(skip)?.checked_mul(SHARD_BLOCK_SIZE)
+    }
+
+    /// Counters of this shard's batch starting at `start`, like [`counter_batch`]
+    ///
+    /// Batches stop at the end of `start`'s block; the returned next counter is
+    /// then the first counter of this shard's following block.
+    pub fn batch(&self, start: u64, batch_size: usize) -> (RangeInclusive<u64>, Option<u64>) {
+        if self.is_all() {
+            return counter_batch(start, batch_size);
+        }
+
+        let block_end = (start / SHARD_BLOCK_SIZE)
+            .checked_add(1)
+            .and_then(|block| block.checked_mul(SHARD_BLOCK_SIZE))
+            .map_or(u64::MAX, |next_block| next_block - 1);
+        let (batch, next) = counter_batch(start, batch_size);
+        let last = (*batch.end()).min(block_end);
+
+        let next = if last < block_end {
+            next
+        } else {
+            last.checked_add(1).and_then(|counter| self.first_at_or_after(counter))
+        };
+        (start..=last, next)
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.number(), self.count)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid shard '{}', expected i/n with 1 <= i <= n (e.g. 2/4)", s);
+        let (number, count) = s.trim().split_once('/').ok_or_else(invalid)?;
+        let number = number.trim().parse::<u64>().map_err(|_| invalid())?;
+        let count = count.trim().parse::<u64>().map_err(|_| invalid())?;
+        Shard::new(number, count).ok_or_else(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const B: u64 = SHARD_BLOCK_SIZE;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("2/4".parse::<Shard>(), Ok(Shard::new(2, 4).unwrap()));
+        assert_eq!(" 1 / 1 ".parse::<Shard>(), Ok(Shard::ALL));
+        assert_eq!(Shard::new(3, 8).unwrap().to_string(), "3/8");
+        assert_eq!(Shard::new(3, 8).unwrap().file_tag(), ".shard3of8");
+        assert_eq!(Shard::ALL.file_tag(), "");
+        for invalid in ["0/4", "5/4", "1/0", "2", "a/b", "-1/2"] {
+            assert!(invalid.parse::<Shard>().is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn test_first_at_or_after() {
+        let shard = Shard::new(2, 3).unwrap();
+        assert_eq!(shard.first_at_or_after(15), Some(B));
+        assert_eq!(shard.first_at_or_after(B + 5), Some(B + 5));
+        assert_eq!(shard.first_at_or_after(2 * B), Some(4 * B));
+        assert_eq!(Shard::new(1, 3).unwrap().first_at_or_after(B + 5), Some(3 * B));
+        assert_eq!(Shard::ALL.first_at_or_after(12345), Some(12345));
+
+        // Nothing left past the last block
+        let last_block = u64::MAX / B;
+        let owner = Shard::new(last_block % 4 + 1, 4).unwrap();
+        let other = Shard::new((last_block + 1) % 4 + 1, 4).unwrap();
+        assert_eq!(owner.first_at_or_after(u64::MAX - 3), Some(u64::MAX - 3));
+        assert_eq!(other.first_at_or_after(u64::MAX - 3), None);
+    }
+
+    #[test]
+    fn test_batch_stops_at_block_end() {
+        let shard = Shard::new(1, 2).unwrap();
+        assert_eq!(shard.batch(10, 100), (10..=109, Some(110)));
+        assert_eq!(shard.batch(B - 50, 100), (B - 50..=B - 1, Some(2 * B)));
+        assert_eq!(Shard::ALL.batch(B - 50, 100), counter_batch(B - 50, 100));
+
+        let last_block = u64::MAX / B;
+        let owner = Shard::new(last_block % 2 + 1, 2).unwrap();
+        assert_eq!(owner.batch(u64::MAX - 5, 100), (u64::MAX - 5..=u64::MAX, None));
+    }
+
+    #[test]
+    fn test_shards_partition_counters() {
+        // Walk every shard from the same start and check that together they cover
+        // each counter of the first blocks exactly once
+        let start = 1_000;
+        let end = 7 * B + 12_345;
+        for count in [1, 2, 3, 5] {
+            let mut covered: Vec<RangeInclusive<u64>> = Vec::new();
+            for number in 1..=count {
+                let shard = Shard::new(number, count).unwrap();
+                let mut next = shard.first_at_or_after(start);
+                while let Some(batch_start) = next
+                    && batch_start <= end
+                {
+                    let (batch, following) = shard.batch(batch_start, 3_000_000);
+                    assert!(shard.contains(*batch.start()) && shard.contains(*batch.end()));
+                    covered.push(*batch.start()..=(*batch.end()).min(end));
+                    next = following;
+                }
+            }
+
+            covered.sort_by_key(|range| *range.start());
+            let mut expected = start;
+            for range in covered {
+                assert_eq!(*range.start(), expected, "gap or overlap with {} shards", count);
+                expected = range.end() + 1;
+            }
+            assert_eq!(expected, end + 1);
+        }
+    }
+}
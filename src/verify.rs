@@ -0,0 +1,58 @@
+//! Proof-of-work verification from the public key alone
+//!
+//! A client's security level only depends on its public key (omega) and counter,
+//! so servers and plugins can check a claimed level without the private key. This
+//! hashes the single message directly with [`sha1_core`](crate::hashers::sha1_core)
+//! instead of setting up a [`SecurityLevelHasher`](crate::SecurityLevelHasher).
+
+use crate::hashers::sha1_core::Sha1;
+use crate::helpers::{count_trailing_zero_bits, format_counter};
+
+/// Security level of `omega` with `counter`: trailing zero bits of SHA-1(omega || counter)
+pub fn verify_security_level(omega: &str, counter: u64) -> u8 {
+    let mut digits = [0u8; 20];
+    let mut sha1 = Sha1::new();
+    sha1.update(omega.as_bytes());
+    sha1.update(format_counter(counter, &mut digits));
+    count_trailing_zero_bits(&sha1.finalize())
+}
+
+/// Whether `omega` with `counter` reaches at least `min_level`
+pub fn meets_level(omega: &str, counter: u64, min_level: u8) -> bool {
+    verify_security_level(omega, counter) >= min_level
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::TEST_PUBLIC_KEY;
+    use crate::hashers::CpuHasher;
+    use crate::identity::Ts3Identity;
+    use crate::level_improver::SecurityLevelHasher;
+
+    #[test]
+    fn test_known_levels() {
+        assert_eq!(verify_security_level(TEST_PUBLIC_KEY, 14), 8);
+        assert_eq!(verify_security_level(TEST_PUBLIC_KEY, 201), 9);
+        assert_eq!(verify_security_level(TEST_PUBLIC_KEY, 672), 12);
+
+        let identity = Ts3Identity::from_file("inis/level22.ini").unwrap();
+        assert_eq!(verify_security_level(&identity.public_key_base64(), identity.counter), 22);
+    }
+
+    #[test]
+    fn test_meets_level() {
+        assert!(meets_level(TEST_PUBLIC_KEY, 672, 12));
+        assert!(meets_level(TEST_PUBLIC_KEY, 672, 8));
+        assert!(!meets_level(TEST_PUBLIC_KEY, 672, 13));
+        assert!(meets_level(TEST_PUBLIC_KEY, 0, 0));
+    }
+
+    #[test]
+    fn test_matches_cpu_hasher() {
+        let counters: Vec<u64> = (0..1000).chain([9_999_999_999, u64::MAX - 1, u64::MAX]).collect();
+        for counter in counters {
+            assert_eq!(verify_security_level(TEST_PUBLIC_KEY, counter), CpuHasher.calculate_level(TEST_PUBLIC_KEY, counter));
+        }
+    }
+}
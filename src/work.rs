@@ -0,0 +1,366 @@
+//! Offline work packages for improving identities on air-gapped machines
+//!
+//! `export-work` writes a [`WorkPackage`] holding only the public key, the starting
+//! counter and the target level. A worker answers it with a [`WorkResult`], and
+//! `import-result` re-checks the result with `CpuHasher` before putting the counter
+//! into the identity. Both files are small INI files without any secret, so they can
+//! be carried over on removable media.
+//!
+//! The worker has no key to sign with, so the result is sealed with a SHA-1 checksum
+//! over its fields. That catches corruption and casual edits; what makes a result
+//! trustworthy is re-hashing its counter on import.
+
+use std::path::{Path, PathBuf};
+use ini::{Ini, Properties};
+use thiserror::Error;
+use crate::hashers::CpuHasher;
+use crate::hashers::sha1_core;
+use crate::identity::{public_key_uid, Ts3Identity};
+use crate::level_improver::{LevelSearchResult, SecurityLevelHasher};
+
+/// Format marker written into every package and result
+pub const WORK_FORMAT: &str = "ts3-sec-work/1";
+
+#[derive(Debug, Error)]
+pub enum WorkError {
+    #[error("Failed to access work file: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("Failed to parse work file: {0}")]
+    IniError(String),
+
+    #[error("Missing [{0}] section in file")]
+    MissingSection(&'static str),
+
+    #[error("Missing '{0}' key in work file")]
+    MissingKey(&'static str),
+
+    #[error("Invalid value for '{0}': {1}")]
+    InvalidValue(&'static str, String),
+
+    #[error("Unsupported work file format '{0}' (expected {WORK_FORMAT})")]
+    UnsupportedFormat(String),
+
+    #[error("Fingerprint does not match the public key")]
+    FingerprintMismatch,
+
+    #[error("Result checksum does not match its contents")]
+    ChecksumMismatch,
+
+    #[error("Result was computed for a different public key (UID {0})")]
+    PublicKeyMismatch(String),
+
+    #[error("Counter {counter} has security level {actual}, the result claims {claimed}")]
+    LevelMismatch { counter: u64, claimed: u8, actual: u8 },
+}
+
+/// A counter search for one public key, as handed to a worker
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkPackage {
+    /// Public key (omega) to search counters for
+    pub public_key: String,
+    /// Counter the identity has now; the worker starts after it
+    pub counter: u64,
+    pub target_level: u8,
+    /// UID of the public key, identifying the identity without revealing anything
+    pub fingerprint: String,
+}
+
+impl WorkPackage {
+    const SECTION: &'static str = "WorkPackage";
+
+    /// Package the search for `identity` up to `target_level`
+    pub fn for_identity(identity: &Ts3Identity, target_level: u8) -> Self {
+        Self {
+            public_key: identity.public_key_base64(),
+            counter: identity.counter,
+            target_level,
+            fingerprint: identity.uid(),
+        }
+    }
+
+    /// Default package path for an identity file: `<dir>/<basename>.work`
+    pub fn path_for<P: AsRef<Path>>(identity_path: P) -> PathBuf {
+        identity_path.as_ref().with_extension("work")
+    }
+
+    /// Load a package, checking its format and fingerprint
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WorkError> {
+        let conf = load_ini(path)?;
+        let section = section(&conf, Self::SECTION)?;
+
+        let package = Self {
+            public_key: get(section, "public_key")?.to_string(),
+            counter: parse(section, "counter")?,
+            target_level: parse(section, "target_level")?,
+            fingerprint: get(section, "fingerprint")?.to_string(),
+        };
+        if public_key_uid(&package.public_key) != package.fingerprint {
+            return Err(WorkError::FingerprintMismatch);
+        }
+        Ok(package)
+    }
+
+    /// Save the package to a file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WorkError> {
+        let mut conf = Ini::new();
+        conf.with_section(Some(Self::SECTION))
+            .set("format", WORK_FORMAT)
+            .set("public_key", self.public_key.as_str())
+            .set("counter", self.counter.to_string())
+            .set("target_level", self.target_level.to_string())
+            .set("fingerprint", self.fingerprint.as_str());
+        conf.write_to_file(path)?;
+        Ok(())
+    }
+}
+
+/// The outcome of a worker's search, sealed with a checksum
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkResult {
+    pub public_key: String,
+    pub fingerprint: String,
+    /// Counter and target of the package this answers
+    pub start_counter: u64,
+    pub target_level: u8,
+    /// Best counter found and its security level
+    pub counter: u64,
+    pub level: u8,
+    pub hashes_checked: u64,
+    pub elapsed_secs: f64,
+    /// Hasher the worker used (informational)
+    pub hasher: String,
+}
+
+impl WorkResult {
+    const SECTION: &'static str = "WorkResult";
+
+    /// Result for `package` with the best counter found by the worker
+    pub fn new(package: &WorkPackage, best: LevelSearchResult, hashes_checked: u64, elapsed_secs: f64, hasher: &str) -> Self {
+        Self {
+            public_key: package.public_key.clone(),
+            fingerprint: package.fingerprint.clone(),
+            start_counter: package.counter,
+            target_level: package.target_level,
+            counter: best.counter,
+            level: best.level,
+            hashes_checked,
+            elapsed_secs,
+            hasher: hasher.to_string(),
+        }
+    }
+
+    /// Default result path for a package: `<dir>/<basename>.result`
+    pub fn path_for<P: AsRef<Path>>(package_path: P) -> PathBuf {
+        package_path.as_ref().with_extension("result")
+    }
+
+    /// Whether the result reaches the package's target level
+    pub fn target_reached(&self) -> bool {
+        self.level >= self.target_level
+    }
+
+    /// Fields in file order, as covered by the checksum
+    fn fields(&self) -> [(&'static str, String); 10] {
+        [
+            ("format", WORK_FORMAT.to_string()),
+            ("public_key", self.public_key.clone()),
+            ("fingerprint", self.fingerprint.clone()),
+            ("start_counter", self.start_counter.to_string()),
+            ("target_level", self.target_level.to_string()),
+            ("counter", self.counter.to_string()),
+            ("level", self.level.to_string()),
+            ("hashes_checked", self.hashes_checked.to_string()),
+            ("elapsed_secs", format!("{:.3}", self.elapsed_secs)),
+            ("hasher", self.hasher.clone()),
+        ]
+    }
+
+    /// Hex SHA-1 over the `key=value` lines of all fields
+    fn checksum(&self) -> String {
+        let canonical: String = self.fields().iter()
+            .map(|(key, value)| format!("{}={}\n", key, value))
+            .collect();
+        sha1_core::digest(canonical.as_bytes()).iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Load a result, checking its format, fingerprint and checksum
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, WorkError> {
+        let conf = load_ini(path)?;
+        let section = section(&conf, Self::SECTION)?;
+
+        let result = Self {
+            public_key: get(section, "public_key")?.to_string(),
+            fingerprint: get(section, "fingerprint")?.to_string(),
+            start_counter: parse(section, "start_counter")?,
+            target_level: parse(section, "target_level")?,
+            counter: parse(section, "counter")?,
+            level: parse(section, "level")?,
+            hashes_checked: parse(section, "hashes_checked")?,
+            elapsed_secs: parse(section, "elapsed_secs")?,
+            hasher: get(section, "hasher")?.to_string(),
+        };
+        if public_key_uid(&result.public_key) != result.fingerprint {
+            return Err(WorkError::FingerprintMismatch);
+        }
+        if get(section, "checksum")? != result.checksum() {
+            return Err(WorkError::ChecksumMismatch);
+        }
+        Ok(result)
+    }
+
+    /// Save the result with its checksum
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), WorkError> {
+        let mut conf = Ini::new();
+        let mut section = conf.with_section(Some(Self::SECTION));
+        for (key, value) in self.fields() {
+            section.set(key, value);
+        }
+        section.set("checksum", self.checksum());
+        conf.write_to_file(path)?;
+        Ok(())
+    }
+
+    /// Re-hash the claimed counter with `CpuHasher`
+    pub fn validate(&self) -> Result<(), WorkError> {
+        let actual = CpuHasher.calculate_level(&self.public_key, self.counter);
+        if actual != self.level {
+            return Err(WorkError::LevelMismatch { counter: self.counter, claimed: self.level, actual });
+        }
+        Ok(())
+    }
+
+    /// Put the result's counter into `identity` if it is valid and better
+    ///
+    /// Fails if the result belongs to a different public key or does not validate.
+    /// Returns whether the identity was changed.
+    pub fn apply_to(&self, identity: &mut Ts3Identity) -> Result<bool, WorkError> {
+        if identity.public_key_base64() != self.public_key {
+            return Err(WorkError::PublicKeyMismatch(self.fingerprint.clone()));
+        }
+        self.validate()?;
+
+        if self.level <= identity.security_level() {
+            return Ok(false);
+        }
+        identity.counter = self.counter;
+        Ok(true)
+    }
+}
+
+fn load_ini<P: AsRef<Path>>(path: P) -> Result<Ini, WorkError> {
+    Ini::load_from_file(path).map_err(|e| match e {
+        ini::Error::Io(e) => WorkError::IoError(e),
+        e => WorkError::IniError(e.to_string()),
+    })
+}
+
+/// The named section, after checking the file's format marker
+fn section<'a>(conf: &'a Ini, name: &'static str) -> Result<&'a Properties, WorkError> {
+    let section = conf.section(Some(name)).ok_or(WorkError::MissingSection(name))?;
+    let format = get(section, "format")?;
+    if format != WORK_FORMAT {
+        return Err(WorkError::UnsupportedFormat(format.to_string()));
+    }
+    Ok(section)
+}
+
+fn get<'a>(section: &'a Properties, key: &'static str) -> Result<&'a str, WorkError> {
+    section.get(key).map(str::trim).ok_or(WorkError::MissingKey(key))
+}
+
+fn parse<T: std::str::FromStr>(section: &Properties, key: &'static str) -> Result<T, WorkError>
+where
+    T::Err: std::fmt::Display,
+{
+    get(section, key)?.parse::<T>()
+        .map_err(|e| WorkError::InvalidValue(key, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::temp_test_dir;
+    use std::fs;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        temp_test_dir(&format!("work-{}", name))
+    }
+
+    fn result_for(package: &WorkPackage, counter: u64, level: u8) -> WorkResult {
+        WorkResult::new(package, LevelSearchResult { counter, level }, 700, 0.5, "CPU")
+    }
+
+    #[test]
+    fn test_package_roundtrip() {
+        let dir = temp_dir("package");
+        let identity = Ts3Identity::from_file("inis/level8.ini").unwrap();
+        let package = WorkPackage::for_identity(&identity, 12);
+        package.save(dir.join("level8.work")).unwrap();
+        let loaded = WorkPackage::load(dir.join("level8.work"));
+        let contents = fs::read_to_string(dir.join("level8.work")).unwrap();
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(loaded.unwrap(), package);
+        assert_eq!(package.counter, 14);
+        assert_eq!(package.fingerprint, "l26GuCq+OpJFe58bQzysddzPoYg=");
+        // Nothing secret may leave the machine
+        assert!(!contents.contains(&identity.to_identity_string()[3..]));
+    }
+
+    #[test]
+    fn test_result_roundtrip_and_checksum() {
+        let dir = temp_dir("result");
+        let identity = Ts3Identity::from_file("inis/level8.ini").unwrap();
+        let package = WorkPackage::for_identity(&identity, 12);
+        let result = result_for(&package, 672, 12);
+        let path = dir.join("level8.result");
+        result.save(&path).unwrap();
+        let loaded = WorkResult::load(&path);
+
+        // Only the level line, not target_level
+        let contents = fs::read_to_string(&path).unwrap();
+        let tampered = contents.replace("\nlevel=12\n", "\nlevel=13\n");
+        fs::write(&path, tampered).unwrap();
+        let tampered = WorkResult::load(&path);
+        fs::remove_dir_all(&dir).ok();
+
+        assert_eq!(loaded.unwrap(), result);
+        assert!(result.target_reached());
+        assert!(contents.contains("\ntarget_level=12\n") && contents.contains("\nlevel=12\n"));
+        assert!(matches!(tampered, Err(WorkError::ChecksumMismatch)));
+    }
+
+    #[test]
+    fn test_apply_to_identity() {
+        let mut identity = Ts3Identity::from_file("inis/level8.ini").unwrap();
+        let package = WorkPackage::for_identity(&identity, 12);
+
+        assert!(!result_for(&package, 14, 8).apply_to(&mut identity).unwrap());
+        assert!(result_for(&package, 672, 12).apply_to(&mut identity).unwrap());
+        assert_eq!((identity.counter, identity.security_level()), (672, 12));
+    }
+
+    #[test]
+    fn test_apply_rejects_wrong_level() {
+        let mut identity = Ts3Identity::from_file("inis/level8.ini").unwrap();
+        let package = WorkPackage::for_identity(&identity, 20);
+
+        let result = result_for(&package, 673, 20).apply_to(&mut identity);
+        assert!(matches!(result, Err(WorkError::LevelMismatch { counter: 673, claimed: 20, .. })));
+        assert_eq!(identity.counter, 14);
+    }
+
+    #[test]
+    fn test_apply_rejects_other_key() {
+        let mut identity = Ts3Identity::from_file("inis/level22.ini").unwrap();
+        let other = Ts3Identity::from_file("inis/level8.ini").unwrap();
+        let package = WorkPackage::for_identity(&other, 12);
+
+        let result = result_for(&package, 672, 12).apply_to(&mut identity);
+        assert!(matches!(result, Err(WorkError::PublicKeyMismatch(_))));
+        assert_eq!(identity.counter, 1118470);
+    }
+}